@@ -0,0 +1,171 @@
+//! One async interface over every backend (feature: `async`)
+//!
+//! [`AsyncEngine`] is the async counterpart of [`Engine`]. [`ThreadedEngine`]
+//! implements it for any sync engine by creating that engine on a dedicated
+//! thread and running each call there, so backends that aren't `Send` (such
+//! as CDP's browser handle) can still be driven from async tasks.
+
+use crate::{Cookie, CookieParam, Engine, EngineConfig, Error, Result, ScriptResult, TextSnapshot};
+use async_trait::async_trait;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+use tokio::sync::oneshot;
+
+/// Async version of the core [`Engine`] operations.
+#[async_trait]
+pub trait AsyncEngine: Send + Sync {
+    /// Navigate to a URL and wait for the page to load
+    async fn load_url(&self, url: &str) -> Result<()>;
+
+    /// Load a document from an HTML string (see [`Engine::load_html`])
+    async fn load_html(&self, html: &str, base_url: Option<&str>) -> Result<()>;
+
+    /// Title, text and URL of the current page
+    async fn render_text_snapshot(&self) -> Result<TextSnapshot>;
+
+    /// PNG screenshot of the current page
+    async fn render_png(&self) -> Result<Vec<u8>>;
+
+    /// Evaluate JavaScript on the current page
+    async fn evaluate_script(&self, script: &str) -> Result<ScriptResult>;
+
+    /// Evaluate JavaScript in the page's own context
+    async fn evaluate_script_in_page(&self, script: &str) -> Result<ScriptResult>;
+
+    /// Cookies visible to the current page
+    async fn get_cookies(&self) -> Result<Vec<Cookie>>;
+
+    /// Set cookies
+    async fn set_cookies(&self, cookies: Vec<CookieParam>) -> Result<()>;
+
+    /// Remove every cookie
+    async fn clear_cookies(&self) -> Result<()>;
+
+    /// Shut the engine down; later calls fail
+    async fn close(&self) -> Result<()>;
+}
+
+enum Job<E> {
+    Run(Box<dyn FnOnce(&mut E) + Send>),
+    Close(oneshot::Sender<Result<()>>),
+}
+
+/// Runs a sync [`Engine`] on its own thread behind the [`AsyncEngine`]
+/// interface. Clones share the engine; it shuts down on
+/// [`close`](AsyncEngine::close) or once every handle is dropped.
+pub struct ThreadedEngine<E> {
+    job_tx: Sender<Job<E>>,
+}
+
+impl<E> Clone for ThreadedEngine<E> {
+    fn clone(&self) -> Self {
+        Self {
+            job_tx: self.job_tx.clone(),
+        }
+    }
+}
+
+impl<E: Engine + 'static> ThreadedEngine<E> {
+    /// Create `E` from `config` on a new thread.
+    pub async fn new(config: EngineConfig) -> Result<Self> {
+        let (job_tx, job_rx) = mpsc::channel::<Job<E>>();
+        let (init_tx, init_rx) = oneshot::channel::<Result<()>>();
+
+        thread::spawn(move || {
+            let mut engine = match E::new(config) {
+                Ok(e) => e,
+                Err(err) => {
+                    let _ = init_tx.send(Err(err));
+                    return;
+                }
+            };
+            let _ = init_tx.send(Ok(()));
+
+            while let Ok(job) = job_rx.recv() {
+                match job {
+                    Job::Run(f) => f(&mut engine),
+                    Job::Close(resp) => {
+                        let _ = resp.send(engine.close());
+                        return;
+                    }
+                }
+            }
+        });
+
+        init_rx
+            .await
+            .map_err(|e| Error::Other(format!("Worker init canceled: {}", e)))??;
+        Ok(Self { job_tx })
+    }
+
+    /// Run `f` against the engine on its thread and await the result. Use
+    /// this for `Engine` methods `AsyncEngine` doesn't cover.
+    pub async fn call<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut E) -> Result<T> + Send + 'static,
+    {
+        let (tx, rx) = oneshot::channel();
+        self.job_tx
+            .send(Job::Run(Box::new(move |engine: &mut E| {
+                let _ = tx.send(f(engine));
+            })))
+            .map_err(|_| Error::Other("Engine is closed".into()))?;
+        rx.await
+            .map_err(|e| Error::Other(format!("Engine call canceled: {}", e)))?
+    }
+}
+
+#[async_trait]
+impl<E: Engine + 'static> AsyncEngine for ThreadedEngine<E> {
+    async fn load_url(&self, url: &str) -> Result<()> {
+        let url = url.to_string();
+        self.call(move |e| e.load_url(&url)).await
+    }
+
+    async fn load_html(&self, html: &str, base_url: Option<&str>) -> Result<()> {
+        let html = html.to_string();
+        let base_url = base_url.map(|s| s.to_string());
+        self.call(move |e| e.load_html(&html, base_url.as_deref()))
+            .await
+    }
+
+    async fn render_text_snapshot(&self) -> Result<TextSnapshot> {
+        self.call(|e| e.render_text_snapshot()).await
+    }
+
+    async fn render_png(&self) -> Result<Vec<u8>> {
+        self.call(|e| e.render_png()).await
+    }
+
+    async fn evaluate_script(&self, script: &str) -> Result<ScriptResult> {
+        let script = script.to_string();
+        self.call(move |e| e.evaluate_script(&script)).await
+    }
+
+    async fn evaluate_script_in_page(&self, script: &str) -> Result<ScriptResult> {
+        let script = script.to_string();
+        self.call(move |e| e.evaluate_script_in_page(&script)).await
+    }
+
+    async fn get_cookies(&self) -> Result<Vec<Cookie>> {
+        self.call(|e| e.get_cookies()).await
+    }
+
+    async fn set_cookies(&self, cookies: Vec<CookieParam>) -> Result<()> {
+        self.call(move |e| e.set_cookies(cookies)).await
+    }
+
+    async fn clear_cookies(&self) -> Result<()> {
+        self.call(|e| e.clear_cookies()).await
+    }
+
+    async fn close(&self) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        self.job_tx
+            .send(Job::Close(tx))
+            .map_err(|_| Error::Other("Engine is closed".into()))?;
+        rx.await
+            .map_err(|e| Error::Other(format!("Close canceled: {}", e)))?
+    }
+}
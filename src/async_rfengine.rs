@@ -0,0 +1,95 @@
+use crate::async_engine::{AsyncEngine, ThreadedEngine};
+use crate::rfengine::RFEngine;
+use crate::{Engine, EngineConfig, Result, TextSnapshot};
+
+/// An async facade over [`RFEngine`], mirroring `async_api::Page` for
+/// callers without Chrome.
+///
+/// A thin wrapper around [`ThreadedEngine<RFEngine>`]: the engine lives on
+/// its own thread and every call runs there, so the futures never block the
+/// executor. Clones share the same engine; it shuts down on
+/// [`close`](Self::close) or once every handle is dropped.
+#[derive(Clone)]
+pub struct AsyncRFEngine {
+    engine: ThreadedEngine<RFEngine>,
+}
+
+impl AsyncRFEngine {
+    /// Create the engine on a new background thread.
+    pub async fn new(config: Option<EngineConfig>) -> Result<Self> {
+        Ok(Self {
+            engine: ThreadedEngine::new(config.unwrap_or_default()).await?,
+        })
+    }
+
+    /// The underlying [`ThreadedEngine`], for the rest of the [`AsyncEngine`]
+    /// interface and [`ThreadedEngine::call`]
+    pub fn engine(&self) -> &ThreadedEngine<RFEngine> {
+        &self.engine
+    }
+
+    /// Navigate to a URL
+    pub async fn goto(&self, url: &str) -> Result<()> {
+        self.engine.load_url(url).await
+    }
+
+    /// Load a document from an HTML string (see [`Engine::load_html`])
+    pub async fn load_html(&self, html: &str, base_url: Option<&str>) -> Result<()> {
+        self.engine.load_html(html, base_url).await
+    }
+
+    /// Evaluate JavaScript and return the serialized result as a string
+    pub async fn eval(&self, script: &str) -> Result<String> {
+        Ok(self.engine.evaluate_script(script).await?.value)
+    }
+
+    /// Evaluate script in the page's context (same as `eval` for RFEngine)
+    pub async fn eval_in_page(&self, script: &str) -> Result<String> {
+        Ok(self.engine.evaluate_script_in_page(script).await?.value)
+    }
+
+    /// Title, text and URL of the current page
+    pub async fn text_snapshot(&self) -> Result<TextSnapshot> {
+        self.engine.render_text_snapshot().await
+    }
+
+    /// Get cookies for the current page
+    pub async fn get_cookies(&self) -> Result<Vec<crate::Cookie>> {
+        self.engine.get_cookies().await
+    }
+
+    /// Set a single cookie (convenience)
+    pub async fn set_cookie(&self, cookie: crate::CookieParam) -> Result<()> {
+        self.engine.set_cookies(vec![cookie]).await
+    }
+
+    /// Delete a cookie by name (domain/path optional to disambiguate)
+    pub async fn delete_cookie(
+        &self,
+        name: &str,
+        url: Option<&str>,
+        domain: Option<&str>,
+        path: Option<&str>,
+    ) -> Result<()> {
+        let (name, url, domain, path) = (
+            name.to_string(),
+            url.map(|s| s.to_string()),
+            domain.map(|s| s.to_string()),
+            path.map(|s| s.to_string()),
+        );
+        self.engine
+            .call(move |e| {
+                e.delete_cookie(&name, url.as_deref(), domain.as_deref(), path.as_deref())
+            })
+            .await
+    }
+
+    pub async fn clear_cookies(&self) -> Result<()> {
+        self.engine.clear_cookies().await
+    }
+
+    /// Shut the engine down. Other clones of this handle stop working.
+    pub async fn close(self) -> Result<()> {
+        self.engine.close().await
+    }
+}
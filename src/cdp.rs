@@ -5,7 +5,9 @@ use headless_chrome::browser::tab::Tab;
 use headless_chrome::browser::tab::{RequestInterceptor, RequestPausedDecision};
 use headless_chrome::protocol::cdp::Fetch::events::RequestPausedEvent;
 use headless_chrome::protocol::cdp::Fetch::{FulfillRequest, HeaderEntry};
+use headless_chrome::protocol::cdp::Network;
 use headless_chrome::protocol::cdp::Page;
+use headless_chrome::protocol::cdp::Runtime;
 use log::warn;
 
 // Type aliases to simplify complex handler types
@@ -13,11 +15,52 @@ type OnLoadHandler = std::sync::Arc<dyn Fn(&crate::TextSnapshot) + Send + Sync>;
 type OnConsoleHandler = std::sync::Arc<dyn Fn(&crate::ConsoleMessage) + Send + Sync>;
 type OnRequestHandler =
     std::sync::Arc<dyn Fn(&crate::RequestInfo) -> crate::RequestAction + Send + Sync>;
+type OnProgressHandler = std::sync::Arc<dyn Fn(crate::LoadProgress) + Send + Sync>;
 use base64::Engine as Base64Engine;
 use headless_chrome::{Browser, LaunchOptions};
 use std::sync::Arc;
 use std::time::Duration;
 
+// Initial delay between `Runtime.evaluate` retries; doubles per attempt.
+const CDP_EVAL_BACKOFF_MS: u64 = 50;
+
+// Convert an evaluation result, reporting thrown exceptions as `is_error`.
+fn script_result(res: Runtime::EvaluateReturnObject) -> ScriptResult {
+    if let Some(details) = res.exception_details {
+        let value = details
+            .exception
+            .and_then(|e| e.description)
+            .unwrap_or(details.text);
+        return ScriptResult {
+            value,
+            is_error: true,
+        };
+    }
+    let value = res
+        .result
+        .value
+        .map(|v| v.to_string())
+        .unwrap_or_else(|| "null".to_string());
+    ScriptResult {
+        value,
+        is_error: false,
+    }
+}
+
+fn to_cookie(c: Network::Cookie) -> crate::Cookie {
+    crate::Cookie {
+        name: c.name,
+        value: c.value,
+        domain: Some(c.domain),
+        path: Some(c.path),
+        expires: Some(c.expires as u64),
+        size: Some(c.size),
+        http_only: Some(c.http_only),
+        secure: Some(c.secure),
+        same_site: c.same_site.map(|s| format!("{:?}", s)),
+    }
+}
+
 /// CDP-based headless engine implementation (uses the `headless_chrome` crate)
 ///
 /// This adapter launches a headless Chrome instance, manages a single tab,
@@ -31,6 +74,7 @@ pub struct CdpEngine {
     on_load: Option<OnLoadHandler>,
     on_console: Option<OnConsoleHandler>,
     on_request: Option<OnRequestHandler>,
+    on_progress: Option<OnProgressHandler>,
 }
 
 impl Engine for CdpEngine {
@@ -95,31 +139,18 @@ impl Engine for CdpEngine {
             on_load: None,
             on_console: None,
             on_request: None,
+            on_progress: None,
         })
     }
 
     fn load_url(&mut self, url: &str) -> Result<()> {
-        let _timeout = Duration::from_millis(self.config.timeout_ms);
-
-        self.tab
-            .navigate_to(url)
-            .map_err(|e| Error::LoadError(format!("Navigation failed: {}", e)))?;
-
-        self.tab
-            .wait_until_navigated()
-            .map_err(|e| Error::LoadError(format!("Wait for navigation failed: {}", e)))?;
-
-        // Wait for the page to stabilize
-        std::thread::sleep(Duration::from_millis(500));
-
-        // Invoke on_load callback if registered
-        if let Some(cb) = &self.on_load {
-            if let Ok(snapshot) = self.render_text_snapshot() {
-                cb(&snapshot);
-            }
-        }
+        let result = self.navigate(url);
+        self.screenshot_on_error(result, "load")
+    }
 
-        Ok(())
+    fn load_html(&mut self, html: &str, base_url: Option<&str>) -> Result<()> {
+        let result = self.set_document(html, base_url);
+        self.screenshot_on_error(result, "load")
     }
 
     fn render_text_snapshot(&self) -> Result<TextSnapshot> {
@@ -161,7 +192,24 @@ impl Engine for CdpEngine {
             }
         };
 
-        Ok(TextSnapshot { title, text, url })
+        // `<html lang>` if set; failures here shouldn't fail the snapshot
+        let html_lang = self
+            .tab
+            .evaluate(
+                "document.documentElement ? document.documentElement.lang : ''",
+                false,
+            )
+            .ok()
+            .and_then(|r| r.value)
+            .and_then(|v| v.as_str().map(|s| s.to_string()));
+        let detected_language = crate::lang::detect_language(html_lang.as_deref(), &text);
+
+        Ok(TextSnapshot {
+            title,
+            text,
+            url,
+            detected_language,
+        })
     }
 
     fn render_png(&self) -> Result<Vec<u8>> {
@@ -174,132 +222,25 @@ impl Engine for CdpEngine {
     }
 
     fn evaluate_script(&mut self, script: &str) -> Result<ScriptResult> {
-        if !self.config.enable_javascript {
-            return Err(Error::ScriptError(
-                "JavaScript execution is disabled in the engine config".into(),
-            ));
+        let result = self.evaluate(script);
+        if matches!(&result, Ok(r) if r.is_error) {
+            self.capture_error_screenshot("script");
         }
-
-        // If JS isolation is enabled, run the script inside a sandboxed iframe
-        if self.config.enable_js_isolation {
-            // Encode script as base64 so it can be embedded safely in srcdoc
-            let b64 = Base64Engine::encode(&base64::engine::general_purpose::STANDARD, script);
-
-            // The iframe posts a JSON-stringified message back to the parent. We build the
-            // wrapper from a template and substitute the base64 script to avoid having to
-            // escape braces for `format!`.
-            let wrapper_template = r#"(async function(){
-                return await new Promise(function(resolve){
-                    const iframe = document.createElement('iframe');
-                    iframe.sandbox = 'allow-scripts';
-                    iframe.style.display = 'none';
-
-                    iframe.srcdoc = '<!doctype html><script>(function(){try{const s=atob("{{B64_TOKEN}}");var _r;try{_r=(function(){return eval(s);})();}catch(e){_r={__rfox_err:String(e)};} var out = (_r && _r.__rfox_err) ? {error: String(_r.__rfox_err)} : {result: _r}; parent.postMessage(JSON.stringify(out), "*");}catch(e){parent.postMessage(JSON.stringify({error: String(e)}),"*");}})();</script>';
-
-                    window.addEventListener('message', function handler(event){
-                        try {
-                            var data = event.data;
-                            if (typeof data === 'string') data = JSON.parse(data);
-                            if (data && (data.result !== undefined || data.error !== undefined)) {
-                                window.removeEventListener('message', handler);
-                                document.body.removeChild(iframe);
-                                try { resolve(JSON.stringify(data)); } catch(e) { resolve(JSON.stringify({error: String(e)})); }
-                            }
-                        } catch(e) {
-                            window.removeEventListener('message', handler);
-                            document.body.removeChild(iframe);
-                            try { resolve(JSON.stringify({error: String(e)})); } catch(e2) { resolve('{"error":"unknown"}'); }
-                        }
-                    }, false);
-
-                    document.body.appendChild(iframe);
-                });
-            })()"#;
-
-            let wrapper = wrapper_template.replace("{{B64_TOKEN}}", &b64);
-
-            let eval_res = self
-                .tab
-                .evaluate(&wrapper, true)
-                .map_err(|e| Error::ScriptError(format!("Island evaluation failed: {}", e)))?;
-
-            let val = eval_res.value.ok_or_else(|| {
-                Error::ScriptError("No value returned from isolated evaluation".into())
-            })?;
-
-            // The iframe now posts a JSON string which is returned as a string value
-            // from CDP; try to parse it into a JSON value for robust processing.
-            let parsed = if val.is_string() {
-                let s = val.as_str().unwrap_or("");
-                match serde_json::from_str::<serde_json::Value>(s) {
-                    Ok(v) => v,
-                    Err(_) => serde_json::Value::String(s.to_string()),
-                }
-            } else {
-                val
-            };
-
-            // The parsed value should be an object with either 'result' or 'error'.
-            if parsed.get("error").is_some() {
-                return Ok(ScriptResult {
-                    value: parsed.get("error").unwrap().to_string(),
-                    is_error: true,
-                });
-            }
-
-            if parsed.get("result").is_some() {
-                return Ok(ScriptResult {
-                    value: parsed.get("result").unwrap().to_string(),
-                    is_error: false,
-                });
-            }
-
-            return Ok(ScriptResult {
-                value: parsed.to_string(),
-                is_error: false,
-            });
-        }
-
-        // Fall back to direct evaluation
-        let result = self
-            .tab
-            .evaluate(script, false)
-            .map_err(|e| Error::ScriptError(format!("Evaluation failed: {}", e)))?;
-
-        let value = result
-            .value
-            .map(|v| v.to_string())
-            .unwrap_or_else(|| "null".to_string());
-
-        Ok(ScriptResult {
-            value,
-            is_error: false,
-        })
+        self.screenshot_on_error(result, "script")
     }
 
     /// Direct page evaluation that runs in the page's global context and can access
     /// DOM properties such as `document.title`. This ignores `enable_js_isolation`.
     fn evaluate_script_in_page(&mut self, script: &str) -> Result<ScriptResult> {
         if !self.config.enable_javascript {
-            return Err(Error::ScriptError(
-                "JavaScript execution is disabled in the engine config".into(),
-            ));
+            return Err(Error::unsupported("evaluate_script_in_page", "CdpEngine"));
         }
 
         let result = self
-            .tab
-            .evaluate(script, true)
+            .evaluate_with_retry(script, true)
             .map_err(|e| Error::ScriptError(format!("Direct evaluation failed: {}", e)))?;
 
-        let value = result
-            .value
-            .map(|v| v.to_string())
-            .unwrap_or_else(|| "null".to_string());
-
-        Ok(ScriptResult {
-            value,
-            is_error: false,
-        })
+        Ok(script_result(result))
     }
 
     fn on_load<F>(&mut self, cb: F)
@@ -364,6 +305,24 @@ impl Engine for CdpEngine {
                             None => String::new(),
                         };
 
+                        let group_depth =
+                            msg.get("depth").and_then(|d| d.as_u64()).unwrap_or(0) as u32;
+                        // Prefer the page-side emission time; fall back to receipt time
+                        let timestamp_ms = msg
+                            .get("ts")
+                            .and_then(|t| t.as_f64())
+                            .map(|t| t as u64)
+                            .unwrap_or_else(crate::unix_millis);
+                        let indent = "  ".repeat(group_depth as usize);
+                        let text = if group_depth == 0 {
+                            text
+                        } else {
+                            text.lines()
+                                .map(|l| format!("{}{}", indent, l))
+                                .collect::<Vec<_>>()
+                                .join("\n")
+                        };
+
                         let cm = crate::ConsoleMessage {
                             level,
                             text,
@@ -371,6 +330,9 @@ impl Engine for CdpEngine {
                             line: None,
                             column: None,
                             stack: None,
+                            group_depth,
+                            timestamp_ms,
+                            frames: Vec::new(),
                         };
                         (handler_arc)(&cm);
                     }
@@ -382,13 +344,27 @@ impl Engine for CdpEngine {
         let wrapper = r#"(function(){
             const rfox_bind = window.__rfox_console;
             if (!rfox_bind) return;
+            let depth = 0;
             ['log','info','warn','error'].forEach(function(k){
                 const orig = console[k];
                 console[k] = function(...args){
-                    try{ rfox_bind(JSON.stringify({ level:k, args: args.map(a=>String(a)) })); }catch(e){}
+                    try{ rfox_bind(JSON.stringify({ level:k, args: args.map(a=>String(a)), depth: depth, ts: Date.now() })); }catch(e){}
                     try{ orig.apply(console, args); }catch(e){}
                 };
             });
+            ['group','groupCollapsed'].forEach(function(k){
+                const orig = console[k];
+                console[k] = function(...args){
+                    if (args.length) { try{ rfox_bind(JSON.stringify({ level:'log', args: args.map(a=>String(a)), depth: depth, ts: Date.now() })); }catch(e){} }
+                    depth++;
+                    try{ orig.apply(console, args); }catch(e){}
+                };
+            });
+            const origGroupEnd = console.groupEnd;
+            console.groupEnd = function(){
+                if (depth > 0) depth--;
+                try{ origGroupEnd.apply(console, []); }catch(e){}
+            };
         })();"#;
 
         let _ = self
@@ -488,30 +464,39 @@ impl Engine for CdpEngine {
         // Note: we don't disable fetch here for simplicity
     }
 
+    fn on_progress<F>(&mut self, cb: F)
+    where
+        F: Fn(crate::LoadProgress) + Send + Sync + 'static,
+    {
+        self.on_progress = Some(Arc::new(cb));
+    }
+
+    fn clear_on_progress(&mut self) {
+        self.on_progress = None;
+    }
+
     fn get_cookies(&self) -> Result<Vec<crate::Cookie>> {
         let cookies = self
             .tab
             .get_cookies()
             .map_err(|e| Error::Other(format!("Failed to get cookies: {}", e)))?;
-        let mapped = cookies
-            .into_iter()
-            .map(|c| crate::Cookie {
-                name: c.name,
-                value: c.value,
-                domain: Some(c.domain),
-                path: Some(c.path),
-                expires: Some(c.expires as u64),
-                size: Some(c.size),
-                http_only: Some(c.http_only),
-                secure: Some(c.secure),
-                same_site: c.same_site.map(|s| format!("{:?}", s)),
+        Ok(cookies.into_iter().map(to_cookie).collect())
+    }
+
+    fn get_cookies_for_url(&self, url: &str) -> Result<Vec<crate::Cookie>> {
+        let res = self
+            .tab
+            .call_method(Network::GetCookies {
+                urls: Some(vec![url.to_string()]),
             })
-            .collect();
-        Ok(mapped)
+            .map_err(|e| Error::Other(format!("Failed to get cookies for {}: {}", url, e)))?;
+        Ok(res.cookies.into_iter().map(to_cookie).collect())
     }
 
     fn set_cookies(&mut self, cookies: Vec<crate::CookieParam>) -> Result<()> {
-        use headless_chrome::protocol::cdp::Network::CookieParam as NetCookieParam;
+        use headless_chrome::protocol::cdp::Network::{
+            CookieParam as NetCookieParam, CookiePartitionKey, CookiePriority,
+        };
         let net_cookies = cookies
             .into_iter()
             .map(|c| NetCookieParam {
@@ -535,11 +520,21 @@ impl Engine for CdpEngine {
                     _ => None,
                 }),
                 expires: c.expires.map(|v| v as f64),
-                priority: None,
+                priority: c.priority.and_then(|p| match p.as_str() {
+                    "Low" | "low" => Some(CookiePriority::Low),
+                    "Medium" | "medium" => Some(CookiePriority::Medium),
+                    "High" | "high" => Some(CookiePriority::High),
+                    _ => None,
+                }),
                 same_party: None,
                 source_scheme: None,
                 source_port: None,
-                partition_key: None,
+                // Chrome computes the cross-site ancestor bit itself for
+                // cookies set from a top-level site
+                partition_key: c.partition_key.map(|site| CookiePartitionKey {
+                    top_level_site: site,
+                    has_cross_site_ancestor: false,
+                }),
             })
             .collect();
 
@@ -583,7 +578,7 @@ impl Engine for CdpEngine {
                 url: None,
                 domain: Some(c.domain),
                 path: Some(c.path),
-                partition_key: None,
+                partition_key: c.partition_key,
             })
             .collect::<Vec<_>>();
 
@@ -593,6 +588,68 @@ impl Engine for CdpEngine {
         Ok(())
     }
 
+    fn extract_jsonld(&self) -> Result<Vec<serde_json::Value>> {
+        // Collect raw block text in the page and parse on the Rust side so invalid
+        // blocks can be skipped without failing the whole extraction.
+        let eval = self
+            .tab
+            .evaluate(
+                r#"JSON.stringify(Array.from(document.querySelectorAll('script[type="application/ld+json"]')).map(function(s){ return s.textContent; }))"#,
+                false,
+            )
+            .map_err(|e| Error::ScriptError(format!("JSON-LD extraction failed: {}", e)))?;
+        let blocks: Vec<String> = eval
+            .value
+            .as_ref()
+            .and_then(|v| v.as_str())
+            .and_then(|s| serde_json::from_str(s).ok())
+            .unwrap_or_default();
+        Ok(blocks
+            .iter()
+            .filter_map(|b| serde_json::from_str(b.trim()).ok())
+            .collect())
+    }
+
+    fn add_init_script(&mut self, source: &str) -> Result<crate::ScriptId> {
+        let res = self
+            .tab
+            .call_method(Page::AddScriptToEvaluateOnNewDocument {
+                source: source.to_string(),
+                world_name: None,
+                include_command_line_api: None,
+                run_immediately: None,
+            })
+            .map_err(|e| Error::CdpError(format!("Failed to add init script: {}", e)))?;
+        Ok(crate::ScriptId::new(res.identifier))
+    }
+
+    fn remove_init_script(&mut self, id: crate::ScriptId) -> Result<()> {
+        self.tab
+            .call_method(Page::RemoveScriptToEvaluateOnNewDocument {
+                identifier: id.as_str().to_string(),
+            })
+            .map_err(|e| Error::CdpError(format!("Failed to remove init script: {}", e)))?;
+        Ok(())
+    }
+
+    fn get_html(&self) -> Result<String> {
+        self.tab
+            .get_content()
+            .map_err(|e| Error::RenderError(format!("Failed to read page HTML: {}", e)))
+    }
+
+    fn content_hash(&self) -> Result<String> {
+        if self.config.content_hash_html {
+            let html = self
+                .tab
+                .get_content()
+                .map_err(|e| Error::RenderError(format!("Failed to read page HTML: {}", e)))?;
+            return Ok(crate::sha256_hex(html.as_bytes()));
+        }
+        let snapshot = self.render_text_snapshot()?;
+        Ok(crate::sha256_hex(snapshot.normalized_content().as_bytes()))
+    }
+
     fn close(self) -> Result<()> {
         // Ensure underlying browser/tab are dropped explicitly so the child
         // process is terminated promptly and to avoid unused-field warnings.
@@ -602,6 +659,278 @@ impl Engine for CdpEngine {
     }
 }
 
+// Inherent helpers behind the `Engine` entry points
+impl CdpEngine {
+    // The engine's tab, shared with `async_api` so it can stop a navigation
+    // while the worker thread is blocked in it
+    pub(crate) fn tab(&self) -> Arc<Tab> {
+        self.tab.clone()
+    }
+
+    fn navigate(&mut self, url: &str) -> Result<()> {
+        let _timeout = Duration::from_millis(self.config.timeout_ms);
+
+        self.emit_progress("navigation_started", false);
+        self.tab.navigate_to(url).map_err(|e| Error::LoadError {
+            url: url.to_string(),
+            reason: format!("Navigation failed: {}", e),
+        })?;
+
+        self.tab
+            .wait_until_navigated()
+            .map_err(|e| Error::LoadError {
+                url: url.to_string(),
+                reason: format!("Wait for navigation failed: {}", e),
+            })?;
+        self.emit_progress("navigated", true);
+
+        // Wait for the page to stabilize
+        std::thread::sleep(Duration::from_millis(500));
+        self.emit_progress("load_complete", true);
+
+        // Invoke on_load callback if registered
+        if let Some(cb) = &self.on_load {
+            if let Ok(snapshot) = self.render_text_snapshot() {
+                cb(&snapshot);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Replace the page with `html` via `Page.setDocumentContent` on a blank
+    // tab; a `<base href>` is prepended so relative URLs use `base_url`.
+    fn set_document(&mut self, html: &str, base_url: Option<&str>) -> Result<()> {
+        self.emit_progress("navigation_started", false);
+        self.tab
+            .navigate_to("about:blank")
+            .and_then(|tab| tab.wait_until_navigated())
+            .map_err(|e| Error::CdpError(format!("Failed to open blank page: {}", e)))?;
+        let tree = self
+            .tab
+            .call_method(Page::GetFrameTree(None))
+            .map_err(|e| Error::CdpError(format!("Failed to get frame tree: {}", e)))?;
+        let html = match base_url {
+            Some(base) => format!(
+                "<base href=\"{}\">{}",
+                base.replace('&', "&amp;").replace('"', "&quot;"),
+                html
+            ),
+            None => html.to_string(),
+        };
+        self.tab
+            .call_method(Page::SetDocumentContent {
+                frame_id: tree.frame_tree.frame.id,
+                html,
+            })
+            .map_err(|e| Error::CdpError(format!("Failed to set document content: {}", e)))?;
+        self.emit_progress("load_complete", true);
+
+        if let Some(cb) = &self.on_load {
+            if let Ok(snapshot) = self.render_text_snapshot() {
+                cb(&snapshot);
+            }
+        }
+        Ok(())
+    }
+
+    fn evaluate(&mut self, script: &str) -> Result<ScriptResult> {
+        if !self.config.enable_javascript {
+            return Err(Error::unsupported("evaluate_script", "CdpEngine"));
+        }
+
+        // If JS isolation is enabled, run the script inside a sandboxed iframe
+        if self.config.enable_js_isolation {
+            // Encode script as base64 so it can be embedded safely in srcdoc
+            let b64 = Base64Engine::encode(&base64::engine::general_purpose::STANDARD, script);
+
+            // The iframe posts a JSON-stringified message back to the parent. We build the
+            // wrapper from a template and substitute the base64 script to avoid having to
+            // escape braces for `format!`.
+            let wrapper_template = r#"(async function(){
+                return await new Promise(function(resolve){
+                    const iframe = document.createElement('iframe');
+                    iframe.sandbox = 'allow-scripts';
+                    iframe.style.display = 'none';
+
+                    iframe.srcdoc = '<!doctype html><script>(function(){try{const s=atob("{{B64_TOKEN}}");var _r;try{_r=(function(){return eval(s);})();}catch(e){_r={__rfox_err:String(e)};} var out = (_r && _r.__rfox_err) ? {error: String(_r.__rfox_err)} : {result: _r}; parent.postMessage(JSON.stringify(out), "*");}catch(e){parent.postMessage(JSON.stringify({error: String(e)}),"*");}})();</script>';
+
+                    window.addEventListener('message', function handler(event){
+                        try {
+                            var data = event.data;
+                            if (typeof data === 'string') data = JSON.parse(data);
+                            if (data && (data.result !== undefined || data.error !== undefined)) {
+                                window.removeEventListener('message', handler);
+                                document.body.removeChild(iframe);
+                                try { resolve(JSON.stringify(data)); } catch(e) { resolve(JSON.stringify({error: String(e)})); }
+                            }
+                        } catch(e) {
+                            window.removeEventListener('message', handler);
+                            document.body.removeChild(iframe);
+                            try { resolve(JSON.stringify({error: String(e)})); } catch(e2) { resolve('{"error":"unknown"}'); }
+                        }
+                    }, false);
+
+                    document.body.appendChild(iframe);
+                });
+            })()"#;
+
+            let wrapper = wrapper_template.replace("{{B64_TOKEN}}", &b64);
+
+            let eval_res = self
+                .evaluate_with_retry(&wrapper, true)
+                .map_err(|e| Error::ScriptError(format!("Island evaluation failed: {}", e)))?;
+            if eval_res.exception_details.is_some() {
+                return Ok(script_result(eval_res));
+            }
+
+            let val = eval_res.result.value.ok_or_else(|| {
+                Error::ScriptError("No value returned from isolated evaluation".into())
+            })?;
+
+            // The iframe now posts a JSON string which is returned as a string value
+            // from CDP; try to parse it into a JSON value for robust processing.
+            let parsed = if val.is_string() {
+                let s = val.as_str().unwrap_or("");
+                match serde_json::from_str::<serde_json::Value>(s) {
+                    Ok(v) => v,
+                    Err(_) => serde_json::Value::String(s.to_string()),
+                }
+            } else {
+                val
+            };
+
+            // The parsed value should be an object with either 'result' or 'error'.
+            if parsed.get("error").is_some() {
+                return Ok(ScriptResult {
+                    value: parsed.get("error").unwrap().to_string(),
+                    is_error: true,
+                });
+            }
+
+            if parsed.get("result").is_some() {
+                return Ok(ScriptResult {
+                    value: parsed.get("result").unwrap().to_string(),
+                    is_error: false,
+                });
+            }
+
+            return Ok(ScriptResult {
+                value: parsed.to_string(),
+                is_error: false,
+            });
+        }
+
+        // Fall back to direct evaluation
+        let result = self
+            .evaluate_with_retry(script, false)
+            .map_err(|e| Error::ScriptError(format!("Evaluation failed: {}", e)))?;
+
+        Ok(script_result(result))
+    }
+
+    // `Runtime.evaluate` with up to `cdp_eval_retries` retries and exponential
+    // backoff. Only transport/protocol failures (e.g. the execution context
+    // going away mid-navigation) are retried; script exceptions come back in
+    // `exception_details` and are returned as-is.
+    fn evaluate_with_retry(
+        &self,
+        expression: &str,
+        await_promise: bool,
+    ) -> anyhow::Result<Runtime::EvaluateReturnObject> {
+        let mut attempt = 0;
+        loop {
+            let res = self.tab.call_method(Runtime::Evaluate {
+                expression: expression.to_string(),
+                return_by_value: Some(false),
+                generate_preview: Some(true),
+                silent: Some(false),
+                await_promise: Some(await_promise),
+                include_command_line_api: Some(false),
+                user_gesture: Some(false),
+                object_group: None,
+                context_id: None,
+                throw_on_side_effect: None,
+                timeout: None,
+                disable_breaks: None,
+                repl_mode: None,
+                allow_unsafe_eval_blocked_by_csp: None,
+                unique_context_id: None,
+                serialization_options: None,
+            });
+            match res {
+                Err(e) if attempt < self.config.cdp_eval_retries => {
+                    let delay = CDP_EVAL_BACKOFF_MS << attempt.min(6);
+                    warn!("CDP evaluate failed ({}); retrying in {}ms", e, delay);
+                    std::thread::sleep(Duration::from_millis(delay));
+                    attempt += 1;
+                }
+                other => return other,
+            }
+        }
+    }
+
+    // Report a load phase to `on_progress`. With `measure`, `bytes_loaded` is
+    // the transfer size of the document and its resources from the Resource
+    // Timing API; before navigation the previous page's entries would be
+    // counted, so it is 0.
+    fn emit_progress(&self, phase: &str, measure: bool) {
+        let Some(cb) = &self.on_progress else {
+            return;
+        };
+        if !measure {
+            cb(crate::LoadProgress {
+                phase: phase.to_string(),
+                bytes_loaded: 0,
+            });
+            return;
+        }
+        let bytes_loaded = self
+            .tab
+            .evaluate(
+                "performance.getEntries().reduce(function(n, e){ return n + (e.transferSize || 0); }, 0)",
+                false,
+            )
+            .ok()
+            .and_then(|r| r.value)
+            .and_then(|v| v.as_u64())
+            .unwrap_or(0);
+        cb(crate::LoadProgress {
+            phase: phase.to_string(),
+            bytes_loaded,
+        });
+    }
+
+    // When `screenshot_on_error_dir` is set, save a screenshot of the current
+    // page before handing a failed `result` back to the caller.
+    fn screenshot_on_error<T>(&self, result: Result<T>, action: &str) -> Result<T> {
+        if result.is_err() {
+            self.capture_error_screenshot(action);
+        }
+        result
+    }
+
+    fn capture_error_screenshot(&self, action: &str) {
+        if let Some(dir) = &self.config.screenshot_on_error_dir {
+            if let Err(e) = self.save_error_screenshot(dir, action) {
+                warn!(
+                    "Failed to save error screenshot to {}: {}",
+                    dir.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    fn save_error_screenshot(&self, dir: &std::path::Path, action: &str) -> Result<()> {
+        let png = self.render_png()?;
+        let path = dir.join(format!("{}-error-{}.png", action, crate::unix_millis()));
+        std::fs::create_dir_all(dir)
+            .and_then(|_| std::fs::write(&path, png))
+            .map_err(|e| Error::Other(format!("{}: {}", path.display(), e)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -620,4 +949,160 @@ mod tests {
         }
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_screenshot_on_script_error() {
+        // Requires Chrome; skipped in CI
+        if std::env::var("CI").is_ok() {
+            return;
+        }
+        let dir = std::env::temp_dir().join(format!("rfheadless-shots-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let config = EngineConfig {
+            enable_js_isolation: false,
+            screenshot_on_error_dir: Some(dir.clone()),
+            ..Default::default()
+        };
+        let mut engine = match CdpEngine::new(config) {
+            Ok(e) => e,
+            Err(e) => {
+                eprintln!(
+                    "Skipping screenshot-on-error test because Chrome is not available: {}",
+                    e
+                );
+                return;
+            }
+        };
+        engine
+            .load_url("data:text/html,<h1>broken</h1>")
+            .expect("Failed to load page");
+        let res = engine
+            .evaluate_script("throw new Error('boom')")
+            .expect("evaluation should report the exception as a result");
+        assert!(res.is_error);
+        assert!(res.value.contains("boom"));
+
+        let shots: Vec<_> = std::fs::read_dir(&dir)
+            .expect("screenshot dir missing")
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "png"))
+            .collect();
+        assert_eq!(shots.len(), 1);
+        let png = std::fs::read(&shots[0]).unwrap();
+        assert_eq!(&png[0..8], b"\x89PNG\r\n\x1a\n");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_init_script_visible_to_page_scripts() {
+        // Requires Chrome; skipped in CI
+        if std::env::var("CI").is_ok() {
+            return;
+        }
+        let mut engine = match CdpEngine::new(EngineConfig::default()) {
+            Ok(e) => e,
+            Err(e) => {
+                eprintln!(
+                    "Skipping init script test because Chrome is not available: {}",
+                    e
+                );
+                return;
+            }
+        };
+        let id = engine
+            .add_init_script("window.__seeded = 42;")
+            .expect("Failed to add init script");
+        let page = "data:text/html,<title>x</title><script>document.title = 'seed:' + window.__seeded;</script>";
+        engine.load_url(page).expect("Failed to load page");
+        assert_eq!(engine.render_text_snapshot().unwrap().title, "seed:42");
+
+        engine
+            .remove_init_script(id)
+            .expect("Failed to remove init script");
+        engine.load_url(page).expect("Failed to load page");
+        assert_eq!(
+            engine.render_text_snapshot().unwrap().title,
+            "seed:undefined"
+        );
+    }
+
+    #[test]
+    fn test_same_site_none_cookie_round_trip() {
+        // Requires Chrome and network access; skipped in CI
+        if std::env::var("CI").is_ok() {
+            return;
+        }
+        let mut engine = match CdpEngine::new(EngineConfig::default()) {
+            Ok(e) => e,
+            Err(e) => {
+                eprintln!(
+                    "Skipping cookie test because Chrome is not available: {}",
+                    e
+                );
+                return;
+            }
+        };
+        if let Err(e) = engine.load_url("https://example.com/") {
+            eprintln!("Skipping cookie test because the page did not load: {}", e);
+            return;
+        }
+        engine
+            .set_cookies(vec![crate::CookieParam {
+                name: "xsite".to_string(),
+                value: "1".to_string(),
+                url: Some("https://example.com/".to_string()),
+                domain: None,
+                path: None,
+                secure: Some(true),
+                http_only: None,
+                same_site: Some("None".to_string()),
+                expires: None,
+                priority: Some("High".to_string()),
+                partition_key: None,
+            }])
+            .expect("Failed to set cookie");
+
+        let cookie = engine
+            .get_cookie_simple("xsite")
+            .expect("Failed to get cookies")
+            .expect("cookie missing");
+        assert_eq!(cookie.value, "1");
+        assert_eq!(cookie.same_site.as_deref(), Some("None"));
+        assert_eq!(cookie.secure, Some(true));
+    }
+
+    #[test]
+    fn test_evaluate_right_after_navigation() {
+        // Requires Chrome; skipped in CI
+        if std::env::var("CI").is_ok() {
+            return;
+        }
+        let config = EngineConfig {
+            enable_js_isolation: false,
+            cdp_eval_retries: 3,
+            ..Default::default()
+        };
+        let mut engine = match CdpEngine::new(config) {
+            Ok(e) => e,
+            Err(e) => {
+                eprintln!(
+                    "Skipping evaluate retry test because Chrome is not available: {}",
+                    e
+                );
+                return;
+            }
+        };
+        for i in 0..5 {
+            engine
+                .tab
+                .navigate_to(&format!("data:text/html,<title>t{}</title>", i))
+                .expect("navigation failed");
+            let res = engine
+                .evaluate_script_in_page("1 + 1")
+                .expect("evaluate during navigation failed");
+            assert!(!res.is_error);
+            assert_eq!(res.value, "2");
+        }
+    }
 }
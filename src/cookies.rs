@@ -0,0 +1,160 @@
+//! Cookie serialization helpers for session import/export
+//!
+//! Two formats are supported: a JSON array of `Cookie` objects and the
+//! Netscape `cookies.txt` layout used by curl and wget (tab-separated
+//! `domain`, `include_subdomains`, `path`, `secure`, `expires`, `name`,
+//! `value`; an `#HttpOnly_` domain prefix marks HTTP-only cookies and an
+//! expiry of 0 marks a session cookie).
+
+use crate::{Cookie, CookieParam, Error, Result};
+
+const HTTP_ONLY_PREFIX: &str = "#HttpOnly_";
+
+/// Parameters that recreate `cookie` via `Engine::set_cookies`.
+pub(crate) fn to_param(cookie: Cookie) -> CookieParam {
+    CookieParam {
+        name: cookie.name,
+        value: cookie.value,
+        url: None,
+        domain: cookie.domain,
+        path: cookie.path,
+        secure: cookie.secure,
+        http_only: cookie.http_only,
+        same_site: cookie.same_site,
+        expires: cookie.expires.filter(|e| *e > 0),
+        priority: None,
+        partition_key: None,
+    }
+}
+
+pub(crate) fn to_json(cookies: &[Cookie]) -> Result<String> {
+    serde_json::to_string_pretty(cookies)
+        .map_err(|e| Error::Other(format!("Failed to serialize cookies: {}", e)))
+}
+
+pub(crate) fn from_json(json: &str) -> Result<Vec<Cookie>> {
+    serde_json::from_str(json)
+        .map_err(|e| Error::ConfigError(format!("Invalid cookie JSON: {}", e)))
+}
+
+pub(crate) fn to_netscape(cookies: &[Cookie]) -> String {
+    let mut out = String::from("# Netscape HTTP Cookie File\n");
+    for c in cookies {
+        let domain = c.domain.as_deref().unwrap_or_default();
+        let prefix = if c.http_only == Some(true) {
+            HTTP_ONLY_PREFIX
+        } else {
+            ""
+        };
+        out.push_str(&format!(
+            "{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+            prefix,
+            domain,
+            flag(domain.starts_with('.')),
+            c.path.as_deref().unwrap_or("/"),
+            flag(c.secure == Some(true)),
+            c.expires.unwrap_or(0),
+            c.name,
+            c.value
+        ));
+    }
+    out
+}
+
+pub(crate) fn from_netscape(text: &str) -> Result<Vec<Cookie>> {
+    let mut cookies = Vec::new();
+    for (lineno, raw) in text.lines().enumerate() {
+        let line = raw.trim_end_matches('\r');
+        let (line, http_only) = match line.strip_prefix(HTTP_ONLY_PREFIX) {
+            Some(rest) => (rest, true),
+            None => (line, false),
+        };
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 7 {
+            return Err(Error::ConfigError(format!(
+                "Invalid cookies.txt line {}: expected 7 tab-separated fields",
+                lineno + 1
+            )));
+        }
+        let expires = fields[4].parse::<u64>().map_err(|_| {
+            Error::ConfigError(format!(
+                "Invalid cookies.txt line {}: bad expiry '{}'",
+                lineno + 1,
+                fields[4]
+            ))
+        })?;
+        cookies.push(Cookie {
+            name: fields[5].to_string(),
+            value: fields[6].to_string(),
+            domain: Some(fields[0].to_string()),
+            path: Some(fields[2].to_string()),
+            expires: Some(expires),
+            size: Some((fields[5].len() + fields[6].len()) as u32),
+            http_only: Some(http_only),
+            secure: Some(fields[3].eq_ignore_ascii_case("TRUE")),
+            same_site: None,
+        });
+    }
+    Ok(cookies)
+}
+
+/// Whether `cookie` has an expiry at or before `now` (Unix seconds). Session
+/// cookies (expiry absent or 0) never expire.
+pub(crate) fn is_expired(cookie: &Cookie, now: u64) -> bool {
+    matches!(cookie.expires, Some(e) if e > 0 && e <= now)
+}
+
+pub(crate) fn unix_secs() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+fn flag(b: bool) -> &'static str {
+    if b {
+        "TRUE"
+    } else {
+        "FALSE"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_netscape_round_trip() {
+        let cookies = vec![
+            Cookie {
+                name: "sid".into(),
+                value: "abc123".into(),
+                domain: Some(".example.com".into()),
+                path: Some("/".into()),
+                expires: Some(1_900_000_000),
+                size: Some(9),
+                http_only: Some(true),
+                secure: Some(true),
+                same_site: None,
+            },
+            Cookie {
+                name: "theme".into(),
+                value: "dark".into(),
+                domain: Some("example.com".into()),
+                path: Some("/app".into()),
+                expires: Some(0),
+                size: Some(9),
+                http_only: Some(false),
+                secure: Some(false),
+                same_site: None,
+            },
+        ];
+        let text = to_netscape(&cookies);
+        assert!(text.contains("#HttpOnly_.example.com\tTRUE\t/\tTRUE\t1900000000\tsid\tabc123"));
+        assert_eq!(from_netscape(&text).unwrap(), cookies);
+        assert!(from_netscape("example.com\tFALSE\t/").is_err());
+    }
+}
@@ -0,0 +1,491 @@
+//! DOM snapshot handed to the page harness
+//!
+//! Scripts see the document as a flat list of elements in document order,
+//! each with its tag, id, class, text, attributes and parent index. Normally
+//! the whole list is serialized into the harness up front. With
+//! `EngineConfig::lazy_dom` the harness instead asks for single elements
+//! (`__rfox_dom_node`) and selector matches (`__rfox_dom_select`), answered
+//! from a [`LazyDom`] parsed on the script worker's thread.
+
+use scraper::{ElementRef, Html, Selector};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+/// Elements of the snapshot in document order with their parent indices,
+/// after `max_dom_elements`/`max_dom_depth` are applied.
+pub(crate) struct DomOrder<'a> {
+    pub(crate) elements: Vec<(ElementRef<'a>, Option<usize>)>,
+    max_elements: usize,
+    max_depth: usize,
+    // Elements left out for being nested deeper than `max_depth`
+    too_deep: usize,
+    hit_element_cap: bool,
+}
+
+/// Walk `document` in the order the harness indexes elements. `0` disables
+/// either limit; `<html>` is at depth 1.
+pub(crate) fn dom_order(document: &Html, max_elements: usize, max_depth: usize) -> DomOrder<'_> {
+    let mut order = DomOrder {
+        elements: Vec::new(),
+        max_elements,
+        max_depth,
+        too_deep: 0,
+        hit_element_cap: false,
+    };
+    let mut stack = vec![(document.root_element(), None, 1)];
+    while let Some((node, parent_idx, depth)) = stack.pop() {
+        if max_elements > 0 && order.elements.len() >= max_elements {
+            order.hit_element_cap = true;
+            break;
+        }
+        let idx = order.elements.len();
+        order.elements.push((node, parent_idx));
+        // Push children with parent index = current idx
+        let children: Vec<_> = node.children().filter_map(ElementRef::wrap).collect();
+        for child in children.into_iter().rev() {
+            if max_depth > 0 && depth >= max_depth {
+                order.too_deep += child
+                    .descendants()
+                    .filter(|n| n.value().is_element())
+                    .count();
+                continue;
+            }
+            stack.push((child, Some(idx), depth + 1));
+        }
+    }
+    order
+}
+
+impl DomOrder<'_> {
+    /// Console warning describing what the limits left out, if anything.
+    pub(crate) fn truncation_warning(&self) -> Option<String> {
+        let mut cuts = Vec::new();
+        if self.too_deep > 0 {
+            cuts.push(format!(
+                "{} elements nested deeper than max_dom_depth ({}) omitted",
+                self.too_deep, self.max_depth
+            ));
+        }
+        if self.hit_element_cap {
+            cuts.push(format!(
+                "stopped at max_dom_elements ({})",
+                self.max_elements
+            ));
+        }
+        if cuts.is_empty() {
+            return None;
+        }
+        Some(format!("DOM snapshot truncated: {}", cuts.join("; ")))
+    }
+}
+
+/// Append the harness JSON object for `node` to `out`. `text_buf` and
+/// `attrs_s` are scratch buffers reused across calls.
+pub(crate) fn push_element_json(
+    out: &mut String,
+    node: ElementRef,
+    parent_idx: Option<usize>,
+    text_buf: &mut String,
+    attrs_s: &mut String,
+) {
+    // Serialize fields, using serde_json::to_string for proper escaping
+    let tag_js = serde_json::to_string(node.value().name()).unwrap_or_else(|_| "\"\"".to_string());
+    let id_js = serde_json::to_string(node.value().attr("id").unwrap_or(""))
+        .unwrap_or_else(|_| "\"\"".to_string());
+    let class_js = serde_json::to_string(node.value().attr("class").unwrap_or(""))
+        .unwrap_or_else(|_| "\"\"".to_string());
+
+    text_buf.clear();
+    for t in node.text() {
+        text_buf.push_str(t);
+    }
+    let text_js = serde_json::to_string(&text_buf).unwrap_or_else(|_| "\"\"".to_string());
+
+    // Attributes as array of [key,value] pairs, sorted by name since scraper
+    // doesn't keep their order stable between parses
+    let mut attrs: Vec<_> = node.value().attrs().collect();
+    attrs.sort_unstable_by_key(|(k, _)| *k);
+    attrs_s.clear();
+    attrs_s.push('[');
+    let mut first_attr = true;
+    for (k, v) in attrs {
+        if !first_attr {
+            attrs_s.push(',');
+        } else {
+            first_attr = false;
+        }
+        let k_js = serde_json::to_string(k).unwrap_or_else(|_| "\"\"".to_string());
+        let v_js = serde_json::to_string(v).unwrap_or_else(|_| "\"\"".to_string());
+        attrs_s.push('[');
+        attrs_s.push_str(&k_js);
+        attrs_s.push(',');
+        attrs_s.push_str(&v_js);
+        attrs_s.push(']');
+    }
+    attrs_s.push(']');
+
+    let parent_js = if let Some(p) = parent_idx {
+        p.to_string()
+    } else {
+        "null".to_string()
+    };
+
+    out.push_str("{\"tag\":");
+    out.push_str(&tag_js);
+    out.push_str(",\"id\":");
+    out.push_str(&id_js);
+    out.push_str(",\"class\":");
+    out.push_str(&class_js);
+    out.push_str(",\"text\":");
+    out.push_str(&text_js);
+    out.push_str(",\"attributes\":");
+    out.push_str(attrs_s);
+    out.push_str(",\"parent\":");
+    out.push_str(&parent_js);
+    out.push('}');
+}
+
+/// A parsed page whose elements are serialized one at a time, on request.
+pub(crate) struct LazyDom {
+    // Identifies the source and limits the page was parsed from
+    key: u64,
+    document: Html,
+    ids: Vec<ego_tree::NodeId>,
+    parents: Vec<Option<usize>>,
+    index: HashMap<ego_tree::NodeId, usize>,
+    /// Number of `node_json` calls so far
+    pub(crate) serialized: usize,
+}
+
+impl LazyDom {
+    /// Parse `html` and index its elements the same way the eager snapshot
+    /// does.
+    pub(crate) fn new(html: &str, max_elements: usize, max_depth: usize) -> Self {
+        let key = lazy_dom_key(html, max_elements, max_depth);
+        let document = Html::parse_document(html);
+        let (ids, parents): (Vec<_>, Vec<_>) = dom_order(&document, max_elements, max_depth)
+            .elements
+            .iter()
+            .map(|(el, parent)| (el.id(), *parent))
+            .unzip();
+        let index = ids.iter().enumerate().map(|(i, id)| (*id, i)).collect();
+        Self {
+            key,
+            document,
+            ids,
+            parents,
+            index,
+            serialized: 0,
+        }
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Harness JSON for element `i`, or `None` past the end.
+    pub(crate) fn node_json(&mut self, i: usize) -> Option<String> {
+        let node = ElementRef::wrap(self.document.tree.get(*self.ids.get(i)?)?)?;
+        let mut out = String::new();
+        push_element_json(
+            &mut out,
+            node,
+            self.parents[i],
+            &mut String::new(),
+            &mut String::new(),
+        );
+        self.serialized += 1;
+        Some(out)
+    }
+
+    /// Indices of the elements matching `selector`, in document order. An
+    /// invalid selector matches nothing.
+    pub(crate) fn select(&self, selector: &str) -> Vec<usize> {
+        let Ok(selector) = Selector::parse(selector) else {
+            return Vec::new();
+        };
+        let mut matches: Vec<usize> = self
+            .document
+            .select(&selector)
+            .filter_map(|el| self.index.get(&el.id()).copied())
+            .collect();
+        matches.sort_unstable();
+        matches
+    }
+}
+
+thread_local! {
+    // The lazily serialized page of the script worker running on this thread
+    // (`Html` is not `Send`, so it stays with the worker's Boa context)
+    static LAZY_DOM: RefCell<Option<LazyDom>> = const { RefCell::new(None) };
+}
+
+/// Run `f` on this thread's lazily serialized page.
+pub(crate) fn with_lazy_dom<R>(f: impl FnOnce(&mut Option<LazyDom>) -> R) -> R {
+    LAZY_DOM.with(|slot| f(&mut slot.borrow_mut()))
+}
+
+fn lazy_dom_key(html: &str, max_elements: usize, max_depth: usize) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    (html, max_elements, max_depth).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Make `html` this thread's lazily serialized page and return its element
+/// count. The harness runs again before every script, so a worker already
+/// holding the same page (with the same limits) keeps it instead of parsing
+/// it again.
+pub(crate) fn open_lazy_dom(html: &str, max_elements: usize, max_depth: usize) -> usize {
+    let key = lazy_dom_key(html, max_elements, max_depth);
+    with_lazy_dom(|slot| {
+        if let Some(dom) = slot.as_ref().filter(|dom| dom.key == key) {
+            return dom.len();
+        }
+        slot.insert(LazyDom::new(html, max_elements, max_depth))
+            .len()
+    })
+}
+
+/// What scripts changed in one element of the snapshot, as reported by the
+/// harness (`__rfox_dom_changes`). `i` is the element's snapshot index.
+#[derive(Debug, Default, serde::Deserialize)]
+pub(crate) struct ElementChange {
+    pub(crate) i: usize,
+    #[serde(default)]
+    pub(crate) removed: bool,
+    /// Markup replacing the element's children
+    pub(crate) content: Option<String>,
+    pub(crate) attributes: Option<Vec<(String, String)>>,
+    /// Markup of the elements appended to it
+    pub(crate) append: Option<String>,
+}
+
+// Elements whose text is written out as-is
+const RAW_TEXT_TAGS: &[&str] = &[
+    "script",
+    "style",
+    "xmp",
+    "iframe",
+    "noembed",
+    "noframes",
+    "noscript",
+    "plaintext",
+];
+const VOID_TAGS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Serialize `document` again with `changes` applied. Indices are resolved
+/// against the same snapshot the harness was given, so `max_elements` and
+/// `max_depth` must match it.
+pub(crate) fn apply_changes(
+    document: &Html,
+    max_elements: usize,
+    max_depth: usize,
+    changes: &[ElementChange],
+) -> String {
+    let order = dom_order(document, max_elements, max_depth);
+    let by_node: HashMap<ego_tree::NodeId, &ElementChange> = changes
+        .iter()
+        .filter_map(|change| {
+            let (el, _) = order.elements.get(change.i)?;
+            Some((el.id(), change))
+        })
+        .collect();
+    let mut out = String::new();
+    for child in document.tree.root().children() {
+        push_node_html(&mut out, child, &by_node);
+    }
+    out
+}
+
+fn push_escaped(out: &mut String, text: &str, attr: bool) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '\u{a0}' => out.push_str("&nbsp;"),
+            '"' if attr => out.push_str("&quot;"),
+            '<' if !attr => out.push_str("&lt;"),
+            '>' if !attr => out.push_str("&gt;"),
+            c => out.push(c),
+        }
+    }
+}
+
+fn push_node_html(
+    out: &mut String,
+    node: ego_tree::NodeRef<scraper::Node>,
+    changes: &HashMap<ego_tree::NodeId, &ElementChange>,
+) {
+    match node.value() {
+        scraper::Node::Doctype(doctype) => {
+            out.push_str("<!DOCTYPE ");
+            out.push_str(doctype.name());
+            out.push('>');
+        }
+        scraper::Node::Comment(comment) => {
+            out.push_str("<!--");
+            out.push_str(comment);
+            out.push_str("-->");
+        }
+        scraper::Node::Text(text) => {
+            let raw = node
+                .parent()
+                .and_then(|p| p.value().as_element())
+                .is_some_and(|el| RAW_TEXT_TAGS.contains(&el.name()));
+            if raw {
+                out.push_str(text);
+            } else {
+                push_escaped(out, text, false);
+            }
+        }
+        scraper::Node::Element(el) => {
+            let change = changes.get(&node.id());
+            if change.is_some_and(|c| c.removed) {
+                return;
+            }
+            out.push('<');
+            out.push_str(el.name());
+            let mut push_attr = |k: &str, v: &str| {
+                out.push(' ');
+                out.push_str(k);
+                out.push_str("=\"");
+                push_escaped(out, v, true);
+                out.push('"');
+            };
+            match change.and_then(|c| c.attributes.as_ref()) {
+                Some(attrs) => attrs.iter().for_each(|(k, v)| push_attr(k, v)),
+                None => el.attrs().for_each(|(k, v)| push_attr(k, v)),
+            }
+            out.push('>');
+            if VOID_TAGS.contains(&el.name()) {
+                return;
+            }
+            match change.and_then(|c| c.content.as_deref()) {
+                Some(content) => out.push_str(content),
+                None => {
+                    for child in node.children() {
+                        push_node_html(out, child, changes);
+                    }
+                }
+            }
+            if let Some(append) = change.and_then(|c| c.append.as_deref()) {
+                out.push_str(append);
+            }
+            out.push_str("</");
+            out.push_str(el.name());
+            out.push('>');
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lazy_dom_matches_eager_snapshot() {
+        let html = r#"<html><head><title>T</title></head><body>
+            <div id="main" class="box"><p data-x="1">One</p><p>Two <b>bold</b></p></div>
+            <ul><li>a</li><li class="box">b</li></ul></body></html>"#;
+        let document = Html::parse_document(html);
+        let eager: Vec<String> = dom_order(&document, 0, 0)
+            .elements
+            .into_iter()
+            .map(|(el, parent)| {
+                let mut out = String::new();
+                push_element_json(&mut out, el, parent, &mut String::new(), &mut String::new());
+                out
+            })
+            .collect();
+
+        let mut lazy = LazyDom::new(html, 0, 0);
+        assert_eq!(lazy.len(), eager.len());
+        assert_eq!(lazy.serialized, 0);
+        let boxes = lazy.select(".box");
+        assert_eq!(boxes.len(), 2);
+        for &i in &boxes {
+            assert_eq!(lazy.node_json(i).as_deref(), Some(eager[i].as_str()));
+        }
+        // Only the queried elements were serialized
+        assert_eq!(lazy.serialized, 2);
+        for (i, json) in eager.iter().enumerate() {
+            assert_eq!(lazy.node_json(i).as_deref(), Some(json.as_str()));
+        }
+        assert_eq!(lazy.node_json(eager.len()), None);
+        assert!(lazy.select("p[").is_empty());
+    }
+
+    #[test]
+    fn test_open_lazy_dom_reuses_the_same_page() {
+        let html = "<html><body><p>One</p><p>Two</p></body></html>";
+        assert_eq!(open_lazy_dom(html, 0, 0), 5);
+        with_lazy_dom(|slot| slot.as_mut().unwrap().serialized = 7);
+        // Same page again: the parsed one is kept
+        assert_eq!(open_lazy_dom(html, 0, 0), 5);
+        assert_eq!(with_lazy_dom(|slot| slot.as_ref().unwrap().serialized), 7);
+        // Other limits or another page are parsed afresh
+        assert_eq!(open_lazy_dom(html, 3, 0), 3);
+        assert_eq!(with_lazy_dom(|slot| slot.as_ref().unwrap().serialized), 0);
+        assert_eq!(open_lazy_dom("<html><body></body></html>", 0, 0), 3);
+    }
+
+    #[test]
+    fn test_apply_changes_rewrites_the_page() {
+        let html = r#"<!DOCTYPE html><html><head><title>T</title><script>if (a < b) {}</script></head><body>
+            <div id="main" class="box"><p>One</p><p>Two &amp; <b>bold</b></p></div>
+            <!-- note --><ul><li>a</li></ul></body></html>"#;
+        let document = Html::parse_document(html);
+        let unchanged = apply_changes(&document, 0, 0, &[]);
+        assert!(unchanged.starts_with("<!DOCTYPE html><html><head><title>T</title>"));
+        assert!(unchanged.contains("<script>if (a < b) {}</script>"));
+        assert!(unchanged.contains("<p>Two &amp; <b>bold</b></p>"));
+        assert!(unchanged.contains("<!-- note -->"));
+
+        // html, head, title, script, body, div, p, p, b, ul, li
+        let changes = vec![
+            ElementChange {
+                i: 5,
+                attributes: Some(vec![
+                    ("id".into(), "main".into()),
+                    ("data-k".into(), "\"1\"".into()),
+                ]),
+                append: Some("<p>Three</p>".into()),
+                ..Default::default()
+            },
+            ElementChange {
+                i: 6,
+                content: Some("x &lt; y".into()),
+                ..Default::default()
+            },
+            ElementChange {
+                i: 9,
+                removed: true,
+                ..Default::default()
+            },
+            ElementChange {
+                i: 10,
+                removed: true,
+                ..Default::default()
+            },
+            // Not in the snapshot
+            ElementChange {
+                i: 42,
+                removed: true,
+                ..Default::default()
+            },
+        ];
+        let changed = apply_changes(&document, 0, 0, &changes);
+        assert!(changed.contains(
+            r#"<div id="main" data-k="&quot;1&quot;"><p>x &lt; y</p><p>Two &amp; <b>bold</b></p><p>Three</p></div>"#
+        ));
+        assert!(!changed.contains("<ul>"));
+        let reparsed = Html::parse_document(&changed);
+        let text: String = reparsed.root_element().text().collect();
+        assert!(text.contains("x < y"));
+        assert!(!text.contains("One"));
+    }
+}
@@ -13,8 +13,13 @@ pub enum Error {
     InitializationError(String),
 
     /// Failed to load a URL
-    #[error("Failed to load URL: {0}")]
-    LoadError(String),
+    #[error("Failed to load URL {url}: {reason}")]
+    LoadError {
+        /// The URL that was being loaded
+        url: String,
+        /// Underlying cause (transport, body read or navigation failure)
+        reason: String,
+    },
 
     /// Failed to render content
     #[error("Rendering failed: {0}")]
@@ -41,11 +46,32 @@ pub enum Error {
     #[error("CDP error: {0}")]
     CdpError(String),
 
+    /// The operation is not available on this backend, either because it is
+    /// not implemented or because the configuration disables it (e.g.
+    /// `evaluate_script` with `enable_javascript = false`)
+    #[error("{operation} is unavailable on {backend} (unsupported or disabled)")]
+    Unsupported {
+        /// Engine method that was called, e.g. `"render_png"`
+        operation: String,
+        /// Backend type name, e.g. `"SimpleEngine"`
+        backend: String,
+    },
+
     /// Generic error
     #[error("{0}")]
     Other(String),
 }
 
+impl Error {
+    /// Shorthand for `Error::Unsupported`.
+    pub(crate) fn unsupported(operation: &str, backend: &str) -> Self {
+        Error::Unsupported {
+            operation: operation.to_string(),
+            backend: backend.to_string(),
+        }
+    }
+}
+
 #[cfg(feature = "cdp")]
 impl From<anyhow::Error> for Error {
     fn from(err: anyhow::Error) -> Self {
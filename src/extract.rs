@@ -0,0 +1,519 @@
+//! Structured-data extraction over a parsed HTML document
+//!
+//! Shared by the scraper-based backends (`RFEngine` and the standalone
+//! `SimpleEngine`); the CDP backend extracts in the browser instead.
+
+use crate::{Error, Result};
+use scraper::{ElementRef, Html, Selector};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+static JSONLD_SELECTOR: OnceLock<Selector> = OnceLock::new();
+
+fn jsonld_selector() -> &'static Selector {
+    JSONLD_SELECTOR.get_or_init(|| Selector::parse("script[type=\"application/ld+json\"]").unwrap())
+}
+
+/// Parse each JSON-LD script block, skipping blocks that aren't valid JSON.
+pub(crate) fn jsonld(document: &Html) -> Vec<serde_json::Value> {
+    document
+        .select(jsonld_selector())
+        .filter_map(|el| serde_json::from_str(el.text().collect::<String>().trim()).ok())
+        .collect()
+}
+
+/// Build the microdata JSON form (`{"items": [{"type", "id", "properties"}]}`,
+/// as in the WHATWG microdata-to-JSON algorithm) for all top-level items.
+pub(crate) fn microdata(document: &Html) -> Value {
+    let elements: Vec<ElementRef> = document
+        .root_element()
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .collect();
+    let by_id: HashMap<&str, ElementRef> = elements
+        .iter()
+        .filter_map(|e| e.value().attr("id").map(|id| (id, *e)))
+        .collect();
+    let items: Vec<Value> = elements
+        .iter()
+        .filter(|e| e.value().attr("itemscope").is_some() && e.value().attr("itemprop").is_none())
+        .map(|e| microdata_item(*e, &by_id, &mut Vec::new()))
+        .collect();
+    json!({ "items": items })
+}
+
+// `ancestors` holds the items currently being expanded so `itemref` cycles
+// terminate instead of recursing forever.
+fn microdata_item<'a>(
+    item: ElementRef<'a>,
+    by_id: &HashMap<&str, ElementRef<'a>>,
+    ancestors: &mut Vec<ElementRef<'a>>,
+) -> Value {
+    let mut out = Map::new();
+    if let Some(types) = item.value().attr("itemtype") {
+        let types: Vec<&str> = types.split_whitespace().collect();
+        if !types.is_empty() {
+            out.insert("type".into(), json!(types));
+        }
+    }
+    if let Some(id) = item.value().attr("itemid") {
+        out.insert("id".into(), json!(id.trim()));
+    }
+
+    ancestors.push(item);
+    let mut roots: Vec<ElementRef> = item.children().filter_map(ElementRef::wrap).collect();
+    if let Some(refs) = item.value().attr("itemref") {
+        roots.extend(
+            refs.split_whitespace()
+                .filter_map(|id| by_id.get(id).copied()),
+        );
+    }
+    let mut properties = Map::new();
+    let mut stack: Vec<ElementRef> = roots.into_iter().rev().collect();
+    while let Some(el) = stack.pop() {
+        let is_scope = el.value().attr("itemscope").is_some();
+        if let Some(names) = el.value().attr("itemprop") {
+            let value = if !is_scope {
+                Value::String(property_value(el))
+            } else if ancestors.contains(&el) {
+                Value::String("ERROR".into())
+            } else {
+                microdata_item(el, by_id, ancestors)
+            };
+            for name in names.split_whitespace() {
+                if let Value::Array(values) = properties
+                    .entry(name.to_string())
+                    .or_insert_with(|| Value::Array(Vec::new()))
+                {
+                    values.push(value.clone());
+                }
+            }
+        }
+        // Nested items own their descendants
+        if !is_scope {
+            let children: Vec<ElementRef> = el.children().filter_map(ElementRef::wrap).collect();
+            stack.extend(children.into_iter().rev());
+        }
+    }
+    ancestors.pop();
+
+    out.insert("properties".into(), Value::Object(properties));
+    Value::Object(out)
+}
+
+/// Convert the first `<table>` matching `selector` (or the first table inside
+/// the matched element) into one record per body row. Header cells become the
+/// keys, a repeated header getting a numeric suffix (`Name`, `Name_2`);
+/// without a header row, or for cells past the header, keys are positional
+/// (`col0`, `col1`, ...). An invalid selector is an `Error::ConfigError`.
+pub(crate) fn table_records(
+    document: &Html,
+    selector: &str,
+) -> Result<Vec<HashMap<String, String>>> {
+    let sel = Selector::parse(selector)
+        .map_err(|e| Error::ConfigError(format!("Invalid selector '{}': {:?}", selector, e)))?;
+    let matched = document
+        .select(&sel)
+        .next()
+        .ok_or_else(|| Error::Other(format!("No element matches '{}'", selector)))?;
+    let table = if matched.value().name() == "table" {
+        matched
+    } else {
+        matched
+            .descendants()
+            .filter_map(ElementRef::wrap)
+            .find(|e| e.value().name() == "table")
+            .ok_or_else(|| Error::Other(format!("No table found for '{}'", selector)))?
+    };
+
+    // Rows of this table only, not of tables nested inside its cells
+    let rows: Vec<Vec<ElementRef>> = table
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter(|e| e.value().name() == "tr" && owning_table(*e) == Some(table))
+        .map(|tr| {
+            tr.children()
+                .filter_map(ElementRef::wrap)
+                .filter(|c| matches!(c.value().name(), "td" | "th"))
+                .collect::<Vec<_>>()
+        })
+        .filter(|cells| !cells.is_empty())
+        .collect();
+
+    let has_header = rows
+        .first()
+        .map(|cells| cells.iter().all(|c| c.value().name() == "th"))
+        .unwrap_or(false);
+    let headers: Vec<String> = if has_header {
+        unique_headers(rows[0].iter().map(|c| cell_text(*c)))
+    } else {
+        Vec::new()
+    };
+
+    Ok(rows
+        .iter()
+        .skip(usize::from(has_header))
+        .map(|cells| {
+            cells
+                .iter()
+                .enumerate()
+                .map(|(i, c)| {
+                    let key = headers
+                        .get(i)
+                        .filter(|h| !h.is_empty())
+                        .cloned()
+                        .unwrap_or_else(|| format!("col{}", i));
+                    (key, cell_text(*c))
+                })
+                .collect()
+        })
+        .collect())
+}
+
+// Suffix repeated header names (`Name`, `Name_2`, `Name_3`) so no column
+// overwrites another; empty headers stay empty and fall back to `colN`
+fn unique_headers(names: impl Iterator<Item = String>) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    names
+        .map(|name| {
+            if name.is_empty() || seen.insert(name.clone()) {
+                return name;
+            }
+            let unique = (2..)
+                .map(|n| format!("{}_{}", name, n))
+                .find(|candidate| !seen.contains(candidate))
+                .unwrap_or_default();
+            seen.insert(unique.clone());
+            unique
+        })
+        .collect()
+}
+
+fn owning_table(el: ElementRef) -> Option<ElementRef> {
+    el.ancestors()
+        .filter_map(ElementRef::wrap)
+        .find(|a| a.value().name() == "table")
+}
+
+// Cell text with runs of whitespace collapsed
+fn cell_text(el: ElementRef) -> String {
+    el.text()
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+// Property value per element type (URL attributes are returned as written).
+fn property_value(el: ElementRef) -> String {
+    let attr = |name: &str| el.value().attr(name).unwrap_or("").to_string();
+    match el.value().name() {
+        "meta" => attr("content"),
+        "audio" | "embed" | "iframe" | "img" | "source" | "track" | "video" => attr("src"),
+        "a" | "area" | "link" => attr("href"),
+        "object" => attr("data"),
+        "data" | "meter" => attr("value"),
+        "time" if el.value().attr("datetime").is_some() => attr("datetime"),
+        _ => el.text().collect::<String>().trim().to_string(),
+    }
+}
+
+/// Find a likely "next page" link and resolve it against the document's
+/// base URL (`<base href>`, else `page_url`). Tries `rel="next"` first, then
+/// links labelled "Next", "›" and the like, then the page after the current
+/// one in numeric pagination.
+pub(crate) fn next_page(document: &Html, page_url: &str) -> Option<String> {
+    let page = reqwest::Url::parse(page_url).ok()?;
+    let base = base_url(document, &page);
+    let resolve = |el: &ElementRef| -> Option<String> {
+        let mut url = base.join(el.value().attr("href")?.trim()).ok()?;
+        if !matches!(url.scheme(), "http" | "https" | "file") {
+            return None;
+        }
+        url.set_fragment(None);
+        let mut current = page.clone();
+        current.set_fragment(None);
+        (url != current).then(|| url.to_string())
+    };
+
+    let links: Vec<ElementRef> = document
+        .select(&Selector::parse("a[href], link[href]").unwrap())
+        .filter(|el| !is_disabled(el))
+        .collect();
+    let by_rel = links.iter().find(|el| {
+        el.value().attr("rel").is_some_and(|rel| {
+            rel.split_whitespace()
+                .any(|r| r.eq_ignore_ascii_case("next"))
+        })
+    });
+    let anchors = || links.iter().filter(|el| el.value().name() == "a");
+    let by_label = || {
+        anchors().find(|el| {
+            let text = cell_text(**el);
+            [
+                Some(text.as_str()),
+                el.value().attr("aria-label"),
+                el.value().attr("title"),
+            ]
+            .into_iter()
+            .flatten()
+            .any(is_next_label)
+                || has_class_token(el, "next")
+                || el
+                    .parent()
+                    .and_then(ElementRef::wrap)
+                    .is_some_and(|p| has_class_token(&p, "next"))
+        })
+    };
+    let by_number = || {
+        let wanted = (current_page_number(document, &page) + 1).to_string();
+        anchors().find(|el| cell_text(**el) == wanted)
+    };
+    by_rel
+        .and_then(resolve)
+        .or_else(|| by_label().and_then(resolve))
+        .or_else(|| by_number().and_then(resolve))
+}
+
+/// URLs of the RSS and Atom feeds the page advertises with
+/// `<link rel="alternate" type="application/rss+xml">` (or `atom+xml`),
+/// resolved against the document's base URL, without duplicates.
+pub(crate) fn feed_links(document: &Html, page_url: &str) -> Vec<String> {
+    let Ok(page) = reqwest::Url::parse(page_url) else {
+        return Vec::new();
+    };
+    let base = base_url(document, &page);
+    let mut feeds: Vec<String> = Vec::new();
+    for link in document.select(&Selector::parse("link[href][type]").unwrap()) {
+        let is_alternate = link.value().attr("rel").is_some_and(|rel| {
+            rel.split_whitespace()
+                .any(|r| r.eq_ignore_ascii_case("alternate"))
+        });
+        let content_type = link.value().attr("type").unwrap_or_default().trim();
+        let is_feed = ["application/rss+xml", "application/atom+xml"]
+            .iter()
+            .any(|t| content_type.eq_ignore_ascii_case(t));
+        if !is_alternate || !is_feed {
+            continue;
+        }
+        if let Ok(url) = base.join(link.value().attr("href").unwrap_or_default().trim()) {
+            let url = url.to_string();
+            if !feeds.contains(&url) {
+                feeds.push(url);
+            }
+        }
+    }
+    feeds
+}
+
+// The first `<base href>` resolved against `page`, else `page` itself
+fn base_url(document: &Html, page: &reqwest::Url) -> reqwest::Url {
+    document
+        .select(&Selector::parse("base[href]").unwrap())
+        .next()
+        .and_then(|b| page.join(b.value().attr("href")?).ok())
+        .unwrap_or_else(|| page.clone())
+}
+
+fn is_next_label(label: &str) -> bool {
+    let label = label.trim().to_lowercase();
+    let word = label.trim_matches(|c: char| c.is_whitespace() || "›»→>".contains(c));
+    if word.is_empty() {
+        return matches!(label.as_str(), "›" | "»" | "→" | ">");
+    }
+    matches!(
+        word,
+        "next" | "next page" | "older posts" | "older entries" | "more results"
+    )
+}
+
+fn has_class_token(el: &ElementRef, token: &str) -> bool {
+    el.value()
+        .attr("class")
+        .is_some_and(|c| c.split_whitespace().any(|t| t.eq_ignore_ascii_case(token)))
+}
+
+fn is_disabled(el: &ElementRef) -> bool {
+    el.value().attr("aria-disabled") == Some("true") || has_class_token(el, "disabled")
+}
+
+// Number of the page being shown: the marked-current pagination item, else a
+// `page`/`p` query parameter or `/page/N` path segment, else 1
+fn current_page_number(document: &Html, page: &reqwest::Url) -> u32 {
+    let marked = Selector::parse("[aria-current=\"page\"], .current, .active, .selected").unwrap();
+    if let Some(n) = document
+        .select(&marked)
+        .find_map(|el| cell_text(el).parse().ok())
+    {
+        return n;
+    }
+    if let Some(n) = page
+        .query_pairs()
+        .find(|(k, _)| matches!(k.as_ref(), "page" | "p" | "pg"))
+        .and_then(|(_, v)| v.parse().ok())
+    {
+        return n;
+    }
+    page.path_segments()
+        .and_then(|segments| {
+            let segments: Vec<&str> = segments.collect();
+            segments
+                .windows(2)
+                .rev()
+                .find(|w| w[0].eq_ignore_ascii_case("page"))
+                .and_then(|w| w[1].parse().ok())
+        })
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_jsonld_skips_invalid_blocks() {
+        let html = r#"<html><head>
+            <script type="application/ld+json">{"@type": "Organization", "name": "RFox"}</script>
+            <script type="application/ld+json">{ not json </script>
+            <script>var ignored = {"@type": "Nope"};</script>
+        </head><body></body></html>"#;
+        let values = jsonld(&Html::parse_document(html));
+        assert_eq!(values.len(), 1);
+        assert_eq!(values[0]["@type"], "Organization");
+    }
+
+    #[test]
+    fn test_microdata_nested_items_and_itemref() {
+        let html = r#"<html><body>
+            <div itemscope itemtype="https://schema.org/Person" itemref="extra">
+              <span itemprop="name">Ada Lovelace</span>
+              <meta itemprop="birthDate" content="1815-12-10">
+              <div itemprop="address" itemscope itemtype="https://schema.org/PostalAddress">
+                <span itemprop="addressLocality">London</span>
+              </div>
+            </div>
+            <p id="extra"><a itemprop="url" href="https://example.com/ada">home</a></p>
+        </body></html>"#;
+        let data = microdata(&Html::parse_document(html));
+        let items = data["items"].as_array().unwrap();
+        assert_eq!(items.len(), 1);
+        let person = &items[0];
+        assert_eq!(person["type"][0], "https://schema.org/Person");
+        assert_eq!(person["properties"]["name"][0], "Ada Lovelace");
+        assert_eq!(person["properties"]["birthDate"][0], "1815-12-10");
+        assert_eq!(person["properties"]["url"][0], "https://example.com/ada");
+        let address = &person["properties"]["address"][0];
+        assert_eq!(address["properties"]["addressLocality"][0], "London");
+        // The nested item's properties don't leak into the parent
+        assert!(person["properties"].get("addressLocality").is_none());
+    }
+
+    #[test]
+    fn test_table_records_with_and_without_header() {
+        let html = r#"<html><body>
+            <table id="prices">
+              <thead><tr><th>Item</th><th>Price</th></tr></thead>
+              <tbody>
+                <tr><td>Apple</td><td>1.20</td></tr>
+                <tr><td>Pear <table><tr><td>nested</td></tr></table></td><td>0.80</td></tr>
+              </tbody>
+            </table>
+            <div class="raw"><table><tr><td>a</td><td>b</td></tr></table></div>
+        </body></html>"#;
+        let doc = Html::parse_document(html);
+
+        let records = table_records(&doc, "#prices").unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0]["Item"], "Apple");
+        assert_eq!(records[0]["Price"], "1.20");
+        assert_eq!(records[1]["Price"], "0.80");
+
+        let raw = table_records(&doc, "div.raw").unwrap();
+        assert_eq!(raw.len(), 1);
+        assert_eq!(raw[0]["col0"], "a");
+        assert_eq!(raw[0]["col1"], "b");
+
+        assert!(table_records(&doc, "#missing").is_err());
+        assert!(matches!(
+            table_records(&doc, "[["),
+            Err(Error::ConfigError(_))
+        ));
+    }
+
+    #[test]
+    fn test_table_records_duplicate_headers() {
+        let html = r#"<table>
+            <tr><th>Name</th><th>Name</th><th>Name_2</th><th>Name</th></tr>
+            <tr><td>a</td><td>b</td><td>c</td><td>d</td></tr>
+        </table>"#;
+        let records = table_records(&Html::parse_document(html), "table").unwrap();
+        assert_eq!(records[0].len(), 4);
+        assert_eq!(records[0]["Name"], "a");
+        assert_eq!(records[0]["Name_2"], "b");
+        assert_eq!(records[0]["Name_2_2"], "c");
+        assert_eq!(records[0]["Name_3"], "d");
+    }
+
+    #[test]
+    fn test_microdata_itemref_cycle_terminates() {
+        let html = r#"<html><body>
+            <div id="a" itemscope itemprop="self" itemref="a"><span itemprop="n">x</span></div>
+            <div itemscope itemref="a"></div>
+        </body></html>"#;
+        let data = microdata(&Html::parse_document(html));
+        assert_eq!(data["items"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_next_page_heuristics() {
+        let page = "https://example.com/blog/page/2/";
+        let next = |html: &str| next_page(&Html::parse_document(html), page);
+        assert_eq!(
+            next(r#"<link rel="prev" href="/blog/"><link rel="next" href="/blog/page/3/">"#),
+            Some("https://example.com/blog/page/3/".into())
+        );
+        assert_eq!(
+            next(r#"<a href="../1/">‹ Prev</a> <a href="../3/">Next ›</a>"#),
+            Some("https://example.com/blog/page/3/".into())
+        );
+        assert_eq!(
+            next(r#"<a href="?sort=new" aria-label="Next page">›</a>"#),
+            Some("https://example.com/blog/page/2/?sort=new".into())
+        );
+        // Numbered links after the page number taken from the URL
+        assert_eq!(
+            next(r#"<a href="/blog/page/1/">1</a> <a href="/blog/page/3/">3</a>"#),
+            Some("https://example.com/blog/page/3/".into())
+        );
+        // The last page: a disabled "Next" and no higher page number
+        assert_eq!(
+            next(
+                r##"<a href="/blog/page/1/">1</a> <span class="current">2</span> <a class="next disabled" href="#">Next</a>"##
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_feed_links_only_rss_and_atom_alternates() {
+        let html = r#"<html><head>
+            <base href="/blog/">
+            <link rel="alternate" type="application/rss+xml" href="feed.xml">
+            <link rel="alternate" type="application/atom+xml" href="https://example.com/atom.xml">
+            <link rel="alternate" type="application/rss+xml" href="/blog/feed.xml">
+            <link rel="alternate" hreflang="de" href="/de/">
+            <link rel="stylesheet" type="text/css" href="site.css">
+            </head></html>"#;
+        assert_eq!(
+            feed_links(
+                &Html::parse_document(html),
+                "https://example.com/index.html"
+            ),
+            [
+                "https://example.com/blog/feed.xml",
+                "https://example.com/atom.xml"
+            ]
+        );
+    }
+}
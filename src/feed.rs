@@ -0,0 +1,164 @@
+//! RSS and Atom parsing for `RFEngine::fetch_feed`
+//!
+//! Like sitemaps, feeds are scanned for the few elements that matter rather
+//! than parsed into a tree: each RSS `<item>` or Atom `<entry>` gives its
+//! title, link and publication date.
+
+use crate::sitemap::unescape;
+
+/// One item of an RSS or Atom feed, as returned by `RFEngine::fetch_feed`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct FeedItem {
+    /// Item title with entities decoded (HTML markup is left as-is)
+    pub title: String,
+    /// Item URL, resolved against the feed URL
+    pub link: Option<String>,
+    /// `pubDate` (RSS) or `published`/`updated` (Atom), as written in the feed
+    pub pub_date: Option<String>,
+}
+
+/// Items of the RSS or Atom document `xml`, in feed order.
+pub(crate) fn parse(xml: &str, feed_url: &str) -> Vec<FeedItem> {
+    let base = url::Url::parse(feed_url).ok();
+    let resolve = |link: String| {
+        base.as_ref()
+            .and_then(|b| b.join(&link).ok())
+            .map(String::from)
+            .unwrap_or(link)
+    };
+    let mut items = elements(xml, "item");
+    if items.is_empty() {
+        items = elements(xml, "entry");
+    }
+    items
+        .into_iter()
+        .map(|(_, item)| FeedItem {
+            title: child_text(item, "title").unwrap_or_default(),
+            link: item_link(item).map(resolve),
+            pub_date: ["pubDate", "published", "updated", "dc:date"]
+                .iter()
+                .find_map(|name| child_text(item, name)),
+        })
+        .collect()
+}
+
+// RSS `<link>` text, or the `href` of an Atom `<link>` that is the entry's
+// alternate (or has no `rel`)
+fn item_link(item: &str) -> Option<String> {
+    let links = elements(item, "link");
+    links
+        .iter()
+        .map(|(_, inner)| unescape(inner.trim()))
+        .find(|text| !text.is_empty())
+        .or_else(|| {
+            links
+                .iter()
+                .filter(|(start, _)| attr(start, "rel").is_none_or(|rel| rel == "alternate"))
+                .find_map(|(start, _)| attr(start, "href"))
+        })
+}
+
+fn child_text(item: &str, name: &str) -> Option<String> {
+    elements(item, name)
+        .first()
+        .map(|(_, inner)| unescape(inner.trim()))
+        .filter(|text| !text.is_empty())
+}
+
+// (start tag, contents) of each `<name>` element in `xml`; nested elements of
+// the same name are not supported, self-closing ones have empty contents
+fn elements<'a>(xml: &'a str, name: &str) -> Vec<(&'a str, &'a str)> {
+    let open = format!("<{}", name);
+    let close = format!("</{}>", name);
+    let mut out = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        rest = &rest[start..];
+        let after = rest[open.len()..].chars().next();
+        if !after.is_some_and(|c| c == '>' || c == '/' || c.is_whitespace()) {
+            // A longer name with the same prefix (`<items>`, `<linkage>`)
+            rest = &rest[open.len()..];
+            continue;
+        }
+        let Some(tag_end) = rest.find('>') else {
+            break;
+        };
+        let start_tag = &rest[..=tag_end];
+        rest = &rest[tag_end + 1..];
+        if start_tag.ends_with("/>") {
+            out.push((start_tag, ""));
+            continue;
+        }
+        let Some(end) = rest.find(&close) else {
+            break;
+        };
+        out.push((start_tag, &rest[..end]));
+        rest = &rest[end + close.len()..];
+    }
+    out
+}
+
+// Value of attribute `name` in a start tag
+fn attr(start_tag: &str, name: &str) -> Option<String> {
+    let mut rest = start_tag;
+    while let Some(pos) = rest.find(name) {
+        let before = rest[..pos].chars().last();
+        rest = &rest[pos + name.len()..];
+        if !before.is_some_and(char::is_whitespace) {
+            continue;
+        }
+        let Some(value) = rest.trim_start().strip_prefix('=') else {
+            continue;
+        };
+        let value = value.trim_start();
+        let quote = value.chars().next().filter(|q| *q == '"' || *q == '\'')?;
+        let value = &value[1..];
+        return value.find(quote).map(|end| unescape(&value[..end]));
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rss_and_atom_items() {
+        let rss = r#"<?xml version="1.0"?><rss version="2.0"><channel>
+            <title>Example</title><link>https://example.com/</link>
+            <atom:link href="https://example.com/feed.xml" rel="self"/>
+            <item><title>Tom &amp; Jerry</title><link>/posts/1</link><pubDate>Mon, 01 Jan 2024 00:00:00 GMT</pubDate></item>
+            <item><title><![CDATA[Fish <em>&</em> chips]]></title><guid>x</guid></item>
+            </channel></rss>"#;
+        assert_eq!(
+            parse(rss, "https://example.com/feed.xml"),
+            [
+                FeedItem {
+                    title: "Tom & Jerry".into(),
+                    link: Some("https://example.com/posts/1".into()),
+                    pub_date: Some("Mon, 01 Jan 2024 00:00:00 GMT".into()),
+                },
+                FeedItem {
+                    title: "Fish <em>&</em> chips".into(),
+                    link: None,
+                    pub_date: None,
+                },
+            ]
+        );
+
+        let atom = r#"<feed xmlns="http://www.w3.org/2005/Atom"><title>Example</title>
+            <link rel="self" href="/atom.xml"/>
+            <entry><title type="html">Caf&#233;</title>
+              <link rel="edit" href="/edit/1"/><link rel="alternate" type="text/html" href="/posts/1"/>
+              <updated>2024-01-02T00:00:00Z</updated><published>2024-01-01T00:00:00Z</published></entry>
+            </feed>"#;
+        assert_eq!(
+            parse(atom, "https://example.com/atom.xml"),
+            [FeedItem {
+                title: "Café".into(),
+                link: Some("https://example.com/posts/1".into()),
+                pub_date: Some("2024-01-01T00:00:00Z".into()),
+            }]
+        );
+    }
+}
@@ -0,0 +1,214 @@
+//! Offline fixtures for RFEngine (`EngineConfig::offline_fixtures`)
+//!
+//! [`FixtureLoader`] is a [`ResourceLoader`] that answers from canned
+//! responses, so tests and replays never touch the network. Fixtures come
+//! from an in-memory map, a directory laid out as `<dir>/<host>/<path>`, or
+//! both (the map is checked first). [`record_response`] writes that layout
+//! for `EngineConfig::record_dir`, so a recorded run can be replayed.
+
+use crate::rfengine::{LoadedResource, ResourceLoader};
+use crate::{Error, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Serves URLs from canned responses; anything without a fixture fails with
+/// `Error::LoadError` instead of going to the network.
+///
+/// In a fixture directory, `https://example.com/css/site.css` is read from
+/// `<dir>/example.com/css/site.css` and a path ending in `/` maps to
+/// `index.html`. The scheme, port, query and fragment are ignored. Status
+/// and headers come from a `<file>.meta.json` sidecar when there is one (as
+/// written by [`record_response`]); otherwise the status is 200 and the
+/// `Content-Type` is guessed from the file extension.
+#[derive(Debug, Clone, Default)]
+pub struct FixtureLoader {
+    map: HashMap<String, LoadedResource>,
+    dir: Option<PathBuf>,
+}
+
+impl FixtureLoader {
+    /// An empty loader; add responses with [`with`](Self::with).
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Serve files from `dir` (read on each request).
+    pub fn from_dir(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            map: HashMap::new(),
+            dir: Some(dir.into()),
+        }
+    }
+
+    /// Serve the bodies in `map`, keyed by absolute URL.
+    pub fn from_map(map: HashMap<String, String>) -> Self {
+        map.into_iter().fold(Self::new(), |loader, (url, body)| {
+            let content_type = content_type_for(&url);
+            loader.with(&url, content_type, body)
+        })
+    }
+
+    /// Answer `url` with a 200 response carrying `body` as `content_type`.
+    pub fn with(mut self, url: &str, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        let headers = HashMap::from([("Content-Type".to_string(), content_type.to_string())]);
+        self.map
+            .insert(fixture_key(url), (200, headers, body.into()));
+        self
+    }
+}
+
+impl ResourceLoader for FixtureLoader {
+    fn fetch(&self, url: &str, _headers: &HashMap<String, String>) -> Result<LoadedResource> {
+        if let Some(resource) = self.map.get(&fixture_key(url)) {
+            return Ok(resource.clone());
+        }
+        let missing = |reason: String| Error::LoadError {
+            url: url.to_string(),
+            reason,
+        };
+        let dir = self
+            .dir
+            .as_deref()
+            .ok_or_else(|| missing("No fixture for this URL".into()))?;
+        let path = fixture_path(dir, url).ok_or_else(|| missing("Not a fixture URL".into()))?;
+        let body = std::fs::read(&path)
+            .map_err(|e| missing(format!("No fixture at {}: {}", path.display(), e)))?;
+        if let Some((status, headers)) = read_meta(&path) {
+            return Ok((status, headers, body));
+        }
+        let headers = HashMap::from([(
+            "Content-Type".to_string(),
+            content_type_for(&path.to_string_lossy()).to_string(),
+        )]);
+        Ok((200, headers, body))
+    }
+}
+
+/// Save a response under `dir` in the [`FixtureLoader`] layout: the body at
+/// `<dir>/<host>/<path>` and the status and headers in a `.meta.json`
+/// sidecar next to it. A later response for the same path replaces it.
+pub fn record_response(
+    dir: &Path,
+    url: &str,
+    status: u16,
+    headers: &HashMap<String, String>,
+    body: &[u8],
+) -> std::io::Result<()> {
+    let path = fixture_path(dir, url).ok_or_else(|| {
+        std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Cannot record {}", url),
+        )
+    })?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&path, body)?;
+    let meta = serde_json::json!({ "url": url, "status": status, "headers": headers });
+    std::fs::write(meta_path(&path), meta.to_string())
+}
+
+fn meta_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".meta.json");
+    path.with_file_name(name)
+}
+
+// Status and headers from the sidecar of a recorded fixture
+fn read_meta(path: &Path) -> Option<(u16, HashMap<String, String>)> {
+    let meta: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(meta_path(path)).ok()?).ok()?;
+    let status = meta.get("status")?.as_u64()?.try_into().ok()?;
+    let headers = serde_json::from_value(meta.get("headers")?.clone()).ok()?;
+    Some((status, headers))
+}
+
+// Map keys ignore the fragment and use the URL's normalized form
+fn fixture_key(url: &str) -> String {
+    match url::Url::parse(url) {
+        Ok(mut parsed) => {
+            parsed.set_fragment(None);
+            parsed.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+// File under `dir` that holds the fixture for `url`
+fn fixture_path(dir: &Path, url: &str) -> Option<PathBuf> {
+    let parsed = url::Url::parse(url).ok()?;
+    let mut path = dir.join(parsed.host_str()?);
+    let segments: Vec<&str> = parsed.path_segments()?.collect();
+    for segment in &segments {
+        // `Url` already resolves dot segments; refuse anything left over
+        if segment.contains(['\\', '/']) || *segment == ".." {
+            return None;
+        }
+        if !segment.is_empty() {
+            path.push(segment);
+        }
+    }
+    if segments.last().is_none_or(|s| s.is_empty()) {
+        path.push("index.html");
+    }
+    Some(path)
+}
+
+fn content_type_for(name: &str) -> &'static str {
+    let name = name.split(['?', '#']).next().unwrap_or(name);
+    let ext = name
+        .rsplit_once('.')
+        .map(|(_, ext)| ext.to_ascii_lowercase());
+    match ext.as_deref() {
+        Some("css") => "text/css; charset=utf-8",
+        Some("js" | "mjs") => "text/javascript; charset=utf-8",
+        Some("json" | "map") => "application/json",
+        Some("svg") => "image/svg+xml",
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("txt") => "text/plain; charset=utf-8",
+        Some("xml") => "application/xml",
+        _ => "text/html",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_path_layout() {
+        let dir = Path::new("/fixtures");
+        assert_eq!(
+            fixture_path(dir, "https://example.com/css/site.css?v=2").unwrap(),
+            Path::new("/fixtures/example.com/css/site.css")
+        );
+        assert_eq!(
+            fixture_path(dir, "http://example.com:8080/docs/").unwrap(),
+            Path::new("/fixtures/example.com/docs/index.html")
+        );
+        assert_eq!(
+            fixture_path(dir, "https://example.com").unwrap(),
+            Path::new("/fixtures/example.com/index.html")
+        );
+        assert!(fixture_path(dir, "data:text/html,hi").is_none());
+    }
+
+    #[test]
+    fn test_map_fixtures_and_missing_urls() {
+        let loader = FixtureLoader::from_map(HashMap::from([(
+            "https://example.com/site.css".to_string(),
+            "p{color:red}".to_string(),
+        )]));
+        let (status, headers, body) = loader
+            .fetch("https://example.com/site.css#top", &HashMap::new())
+            .unwrap();
+        assert_eq!(status, 200);
+        assert_eq!(headers["Content-Type"], "text/css; charset=utf-8");
+        assert_eq!(body, b"p{color:red}");
+        assert!(matches!(
+            loader.fetch("https://example.com/other.css", &HashMap::new()),
+            Err(Error::LoadError { .. })
+        ));
+    }
+}
@@ -0,0 +1,172 @@
+//! Page language detection for `TextSnapshot::detected_language`
+//!
+//! The `<html lang>` attribute wins when present. Otherwise, with the
+//! `lang-detect` feature, a small character-trigram detector guesses the
+//! language of the body text (Cavnar–Trenkle "out-of-place" ranking against
+//! built-in profiles, plus script detection for non-Latin alphabets).
+
+/// Resolve the page language from the document's `lang` attribute, falling
+/// back to text-based detection when the `lang-detect` feature is enabled.
+pub(crate) fn detect_language(html_lang: Option<&str>, text: &str) -> Option<String> {
+    if let Some(lang) = html_lang.map(str::trim).filter(|l| !l.is_empty()) {
+        return Some(lang.to_string());
+    }
+    #[cfg(feature = "lang-detect")]
+    {
+        ngram::detect(text).map(str::to_string)
+    }
+    #[cfg(not(feature = "lang-detect"))]
+    {
+        let _ = text;
+        None
+    }
+}
+
+#[cfg(feature = "lang-detect")]
+mod ngram {
+    use std::collections::HashMap;
+    use std::sync::OnceLock;
+
+    // Ranked trigrams kept per profile; also caps the out-of-place penalty.
+    const PROFILE_SIZE: usize = 300;
+    // Below this many letters the guess is too noisy to report.
+    const MIN_LETTERS: usize = 20;
+
+    // Representative everyday text per language; profiles are built from these
+    // on first use.
+    const SAMPLES: &[(&str, &str)] = &[
+        ("en", "the quick brown fox jumps over the lazy dog. this is a simple sentence that we can use to see what language the text is written in. there are many people who would like to read the news and find out what is happening in the world today, and they have been waiting for it with their friends and family."),
+        ("fr", "le renard brun rapide saute par dessus le chien paresseux. ceci est une phrase simple que nous pouvons utiliser pour savoir dans quelle langue le texte est écrit. il y a beaucoup de gens qui aiment lire les nouvelles et savoir ce qui se passe dans le monde aujourd'hui, avec leurs amis et leur famille."),
+        ("de", "der schnelle braune fuchs springt über den faulen hund. dies ist ein einfacher satz, den wir verwenden können, um zu sehen, in welcher sprache der text geschrieben ist. es gibt viele menschen, die gerne die nachrichten lesen und wissen wollen, was heute in der welt passiert, mit ihren freunden und der familie."),
+        ("es", "el rápido zorro marrón salta sobre el perro perezoso. esta es una frase sencilla que podemos usar para saber en qué idioma está escrito el texto. hay muchas personas que quieren leer las noticias y saber lo que está pasando en el mundo hoy, con sus amigos y su familia."),
+        ("it", "la veloce volpe marrone salta sopra il cane pigro. questa è una frase semplice che possiamo usare per capire in quale lingua è scritto il testo. ci sono molte persone che vogliono leggere le notizie e sapere che cosa sta succedendo nel mondo oggi, con i loro amici e la famiglia."),
+        ("pt", "a rápida raposa marrom salta sobre o cão preguiçoso. esta é uma frase simples que podemos usar para saber em que língua o texto está escrito. há muitas pessoas que gostam de ler as notícias e saber o que está acontecendo no mundo hoje, com os seus amigos e a sua família."),
+        ("nl", "de snelle bruine vos springt over de luie hond. dit is een eenvoudige zin die we kunnen gebruiken om te zien in welke taal de tekst is geschreven. er zijn veel mensen die graag het nieuws lezen en willen weten wat er vandaag in de wereld gebeurt, met hun vrienden en familie."),
+    ];
+
+    fn profiles() -> &'static Vec<(&'static str, HashMap<String, usize>)> {
+        static PROFILES: OnceLock<Vec<(&'static str, HashMap<String, usize>)>> = OnceLock::new();
+        PROFILES.get_or_init(|| {
+            SAMPLES
+                .iter()
+                .map(|(lang, sample)| (*lang, ranks(sample)))
+                .collect()
+        })
+    }
+
+    // Trigram -> rank (0 = most frequent), words padded with spaces.
+    fn ranks(text: &str) -> HashMap<String, usize> {
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for word in text
+            .split(|c: char| !c.is_alphabetic() && c != '\'')
+            .filter(|w| !w.is_empty())
+        {
+            let padded: Vec<char> = format!(" {} ", word.to_lowercase()).chars().collect();
+            for tri in padded.windows(3) {
+                *counts.entry(tri.iter().collect()).or_insert(0) += 1;
+            }
+        }
+        let mut sorted: Vec<(String, usize)> = counts.into_iter().collect();
+        sorted.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        sorted
+            .into_iter()
+            .take(PROFILE_SIZE)
+            .enumerate()
+            .map(|(rank, (tri, _))| (tri, rank))
+            .collect()
+    }
+
+    // Dominant non-Latin script, if it accounts for most letters.
+    fn script_language(text: &str) -> Option<&'static str> {
+        let mut counts: HashMap<&'static str, usize> = HashMap::new();
+        let mut letters = 0usize;
+        for c in text.chars().filter(|c| c.is_alphabetic()) {
+            letters += 1;
+            let lang = match c as u32 {
+                0x0370..=0x03FF => "el",
+                0x0400..=0x04FF => "ru",
+                0x0590..=0x05FF => "he",
+                0x0600..=0x06FF => "ar",
+                0x0E00..=0x0E7F => "th",
+                0x3040..=0x30FF => "ja",
+                0xAC00..=0xD7AF => "ko",
+                0x4E00..=0x9FFF => "zh",
+                _ => continue,
+            };
+            *counts.entry(lang).or_insert(0) += 1;
+        }
+        // Kana marks Japanese even when Han characters dominate
+        if counts.get("ja").copied().unwrap_or(0) * 10 >= letters && counts.contains_key("ja") {
+            return Some("ja");
+        }
+        counts
+            .into_iter()
+            .max_by_key(|(_, n)| *n)
+            .filter(|(_, n)| n * 2 > letters)
+            .map(|(lang, _)| lang)
+    }
+
+    pub(super) fn detect(text: &str) -> Option<&'static str> {
+        if let Some(lang) = script_language(text) {
+            return Some(lang);
+        }
+        if text.chars().filter(|c| c.is_alphabetic()).count() < MIN_LETTERS {
+            return None;
+        }
+        let doc = ranks(text);
+        profiles()
+            .iter()
+            .map(|(lang, profile)| {
+                let distance: usize = doc
+                    .iter()
+                    .map(|(tri, rank)| match profile.get(tri) {
+                        Some(p) => p.abs_diff(*rank),
+                        None => PROFILE_SIZE,
+                    })
+                    .sum();
+                (*lang, distance)
+            })
+            .min_by_key(|(_, d)| *d)
+            .map(|(lang, _)| lang)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_detects_common_languages() {
+            assert_eq!(
+                detect("Where are the people who were waiting for the train this morning?"),
+                Some("en")
+            );
+            assert_eq!(
+                detect("Les enfants jouent dans le jardin pendant que leurs parents préparent le dîner."),
+                Some("fr")
+            );
+            assert_eq!(
+                detect("Die Kinder spielen im Garten, während die Eltern das Abendessen machen."),
+                Some("de")
+            );
+            assert_eq!(detect("Привет, как у тебя дела сегодня?"), Some("ru"));
+            assert_eq!(detect("short"), None);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_html_lang_takes_precedence() {
+        assert_eq!(
+            detect_language(Some(" fr "), "the quick brown fox"),
+            Some("fr".to_string())
+        );
+        assert_eq!(
+            detect_language(Some("pt-BR"), ""),
+            Some("pt-BR".to_string())
+        );
+    }
+}
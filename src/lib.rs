@@ -46,6 +46,18 @@ pub mod simple;
 #[cfg(feature = "rfengine")]
 pub mod rfengine;
 
+// Canned responses for `EngineConfig::offline_fixtures`
+#[cfg(feature = "rfengine")]
+pub mod fixtures;
+
+// Main-content extraction for `RFEngine::extract_article`
+#[cfg(feature = "readability")]
+pub mod readability;
+
+// Source-map resolution for RFEngine console stacks
+#[cfg(feature = "sourcemap")]
+mod source_maps;
+
 // Rendering prototype (Phase 1) — feature-gated under `rfengine` for now
 #[cfg(feature = "rfengine")]
 pub mod rendering;
@@ -53,13 +65,68 @@ pub mod rendering;
 // Platform API surface (service workers, media hooks, accessibility, device emulation)
 pub mod platform;
 
-// Async-friendly browser API (simple worker-backed abstraction)
+// Cookie import/export formats (JSON and Netscape cookies.txt)
+mod cookies;
+
+// Structured-data extraction over parsed HTML documents
+#[cfg(any(feature = "rfengine", feature = "simple"))]
+mod extract;
+
+// XPath subset used by `Engine::query_xpath`
+#[cfg(any(feature = "rfengine", feature = "simple"))]
+mod xpath;
+
+// TypeScript annotation stripping for `EngineConfig::strip_types`
+#[cfg(feature = "ts")]
+mod ts;
+
+// robots.txt rules for `EngineConfig::respect_robots_txt`
+#[cfg(feature = "rfengine")]
+mod robots;
+
+// XML sitemap parsing for `RFEngine::fetch_sitemap`
+#[cfg(feature = "rfengine")]
+mod sitemap;
+
+// DOM snapshot serialization for the page harness (eager and `lazy_dom`)
+#[cfg(feature = "rfengine")]
+mod dom_snapshot;
+
+// RSS/Atom parsing for `RFEngine::fetch_feed`
+#[cfg(feature = "rfengine")]
+pub mod feed;
+
+// Line/word diff used by `TextSnapshot::diff`
+mod text_diff;
+
+// Page language detection used by text snapshots
+#[cfg(any(feature = "rfengine", feature = "simple", feature = "cdp"))]
+mod lang;
+
+// Thread-shareable engine handle (lock-serialized access to any backend)
+pub mod shared;
+pub use shared::SharedEngine;
+
+// Async-friendly browser API (Chrome-specific conveniences over `ThreadedEngine`)
 #[cfg(feature = "cdp")]
 pub mod async_api;
 
 // Re-export the Browser type at the crate root for ergonomic examples
 #[cfg(feature = "cdp")]
 pub use async_api::Browser;
+
+// Backend-independent async interface over any `Engine`
+#[cfg(feature = "async")]
+pub mod async_engine;
+#[cfg(feature = "async")]
+pub use async_engine::{AsyncEngine, ThreadedEngine};
+
+// The same facade over `ThreadedEngine<RFEngine>`, for use without Chrome
+#[cfg(all(feature = "async", feature = "rfengine"))]
+pub mod async_rfengine;
+#[cfg(all(feature = "async", feature = "rfengine"))]
+pub use async_rfengine::AsyncRFEngine;
+
 /// Configuration for the headless engine
 ///
 /// This struct contains the core engine configuration used when creating an
@@ -105,20 +172,217 @@ pub struct EngineConfig {
     /// Optional WebSocket URL to connect to an existing CDP-compatible browser (e.g., ws://...)
     pub cdp_ws_url: Option<String>,
 
-    /// If true, create a persistent Tokio runtime inside the engine for async tasks
+    /// If true, create a persistent Tokio runtime inside the engine for async tasks.
+    /// If false, or when built without the default `async` feature (a warning
+    /// is logged then), RFEngine fetches stylesheets with blocking requests on
+    /// plain threads and never starts a runtime.
     pub enable_persistent_runtime: bool,
 
     /// Maximum concurrent stylesheet fetches when using async fetching
     pub stylesheet_fetch_concurrency: usize,
 
     /// Whether to attempt lightweight preconnect (HEAD) to stylesheet hosts before fetching
-    /// (helps warm TCP/TLS connections). Enabled by default.
+    /// (helps warm TCP/TLS connections). Needs the persistent runtime and the
+    /// `async` feature. Enabled by default.
     pub enable_preconnect: bool,
 
     /// Whether `load_url` should wait for linked stylesheets to finish fetching
     /// before returning. When false, stylesheet fetching runs in background and
     /// `load_url` returns once HTML is parsed. Default: true.
     pub wait_for_stylesheets_on_load: bool,
+
+    /// Restrict the harness-provided globals visible to user scripts (RFEngine).
+    /// When `Some`, only the listed names (e.g. `"document"`, `"console"`) are
+    /// defined; any other harness global such as `setTimeout` and the natives
+    /// behind it are never installed, so neither `setTimeout` nor
+    /// `globalThis.setTimeout` resolves. The `__rfox_*` entry points the engine
+    /// calls are always present. `None` (default) exposes everything.
+    pub script_global_allowlist: Option<Vec<String>>,
+
+    /// Print page console messages to stderr as `[level] text (source:line)` when
+    /// no `on_console` handler is registered (RFEngine). A registered handler
+    /// always takes precedence. Default: false.
+    pub echo_console: bool,
+
+    /// Make `Engine::content_hash` digest the raw page HTML instead of the
+    /// normalized text snapshot. Default: false.
+    pub content_hash_html: bool,
+
+    /// Directory where the CDP backend saves a timestamped PNG screenshot
+    /// whenever `load_url` or `evaluate_script` fails, before the error is
+    /// returned. `None` (default) disables capture.
+    pub screenshot_on_error_dir: Option<std::path::PathBuf>,
+
+    /// How many times the CDP backend retries `Runtime.evaluate` after a
+    /// transport failure (e.g. the page context being replaced during
+    /// navigation), with exponential backoff from 50ms. Script exceptions
+    /// are never retried. Default: 2.
+    pub cdp_eval_retries: u32,
+
+    /// When true, RFEngine returns script results as JSON
+    /// (`JSON.stringify` of the value, `null` when it has none) instead of
+    /// Boa's display form, so objects and strings are unambiguous.
+    /// Default: false.
+    pub json_result_mode: bool,
+
+    /// When true, RFEngine strips TypeScript type annotations and
+    /// `interface`/`type` declarations from scripts before evaluating them.
+    /// Requires the `ts` feature. Default: false.
+    pub strip_types: bool,
+
+    /// Seed for RFEngine's `crypto.getRandomValues` and `Math.random`, making
+    /// scripts that use randomness reproducible. `None` uses an unseeded
+    /// source. Default: None.
+    pub random_seed: Option<u64>,
+
+    /// Maximum number of timer, microtask-batch and animation-frame steps a
+    /// single `__rfox_run_until_idle` call runs in RFEngine before it stops
+    /// with a console warning, so self-rescheduling timers can't pump
+    /// forever. Default: 10000.
+    pub max_event_loop_steps: u32,
+
+    /// When true, RFEngine's `render_text_snapshot` includes text generated
+    /// by `::before`/`::after` `content` rules from the page's stylesheets.
+    /// Default: false.
+    pub include_generated_content: bool,
+
+    /// When true, RFEngine's `load_url` accepts `file://` URLs, reading the
+    /// file and any relative stylesheets from disk. Off by default so
+    /// untrusted URLs can't read local files. Default: false.
+    pub allow_file_urls: bool,
+
+    /// When true, RFEngine fetches `robots.txt` once per origin and refuses
+    /// to load pages it disallows for `user_agent`, failing the load with
+    /// `Error::LoadError`. Disallowed scripts and source maps are skipped.
+    /// A 5xx robots.txt disallows everything but is asked for again on the
+    /// next request. Default: false.
+    pub respect_robots_txt: bool,
+
+    /// Minimum interval in milliseconds between RFEngine requests to the
+    /// same host, across page loads and stylesheet, script and robots.txt
+    /// fetches. 0 disables pacing. Default: 0.
+    pub per_host_delay_ms: u64,
+
+    /// Executable RFEngine runs as `<path> --worker` when
+    /// `use_process_worker` is set. `None` guesses it from
+    /// `CARGO_BIN_EXE_rfheadless`, a sibling `target/*/rfheadless` or the
+    /// current executable. A configured binary that fails to spawn is an
+    /// `Error::InitializationError`; a guessed one falls back to an
+    /// in-thread worker with a warning. Default: None.
+    pub worker_binary_path: Option<std::path::PathBuf>,
+
+    /// Number of extra in-thread Boa contexts RFEngine's `submit_script`
+    /// spreads jobs across, round-robin, so independent scripts run in
+    /// parallel. Pool contexts don't share globals with each other or with
+    /// `evaluate_script`; use `submit_script_pinned` for scripts that depend
+    /// on earlier ones. 0 or 1 disables the pool. Default: 0.
+    pub script_worker_pool_size: usize,
+
+    /// Largest HTML response RFEngine's `load_url` accepts, in bytes. Larger
+    /// responses (by `Content-Length` or by the bytes actually read) fail
+    /// with `Error::LoadError` before they are parsed; the body is read in
+    /// chunks, so an endless stream fails instead of hanging. Page scripts,
+    /// modules and source maps over the limit are skipped. 0 disables the limit.
+    /// Default: 0.
+    pub max_html_bytes: usize,
+
+    /// When true, RFEngine's `load_url` fails with `Error::LoadError` for
+    /// responses whose `Content-Type` is neither HTML nor XML (e.g. JSON or
+    /// images). When false such bodies load as-is and stay readable through
+    /// `Engine::get_html`. Default: false.
+    pub require_html: bool,
+
+    /// Encoding RFEngine decodes a page with when neither `Content-Type`, a
+    /// byte order mark nor a `<meta charset>` declares one, as a WHATWG
+    /// label (`"utf-8"`, `"windows-1252"`, `"shift_jis"`, ...). Unknown
+    /// labels fail `RFEngine::new` with `Error::ConfigError`.
+    /// Default: "utf-8".
+    pub default_charset: String,
+
+    /// Most stylesheet requests RFEngine keeps in flight to one origin at a
+    /// time; further fetches for that origin wait for a free slot, however
+    /// high `stylesheet_fetch_concurrency` is. `0` disables the cap.
+    /// Default: 6.
+    pub max_connections_per_host: usize,
+
+    /// Overall deadline, in milliseconds, for fetching a page's stylesheets
+    /// in RFEngine. Sheets that haven't arrived by then are left out of the
+    /// page instead of each one running into `timeout_ms`. `0` disables the
+    /// deadline. Default: 0.
+    pub stylesheet_phase_timeout_ms: u64,
+
+    /// Largest stylesheet RFEngine applies, in bytes. Bigger sheets are
+    /// skipped; the download stops as soon as it passes the limit. `0`
+    /// disables the limit. Default: 0.
+    pub max_stylesheet_bytes: usize,
+
+    /// Send a HEAD request before each stylesheet and skip the GET when its
+    /// `Content-Length` exceeds `max_stylesheet_bytes`. Has no effect without
+    /// that limit. Default: false.
+    pub probe_stylesheet_size: bool,
+
+    /// Serve RFEngine's documents, stylesheets, scripts and source maps from
+    /// this fixture directory instead of the network (see `fixtures::FixtureLoader`).
+    /// URLs without a fixture fail to load. Default: None.
+    pub offline_fixtures: Option<std::path::PathBuf>,
+
+    /// Directory RFEngine records every document, stylesheet, script and
+    /// source map it fetches from the network into (status, headers and body), in the layout
+    /// `offline_fixtures` replays. Default: None.
+    pub record_dir: Option<std::path::PathBuf>,
+
+    /// CSS selectors whose elements (and everything inside them) RFEngine
+    /// leaves out of `render_text_snapshot`, e.g. `"nav"` or `".ads"`.
+    /// Invalid selectors fail `RFEngine::new` with `Error::ConfigError`.
+    /// Default: empty.
+    pub text_exclude_selectors: Vec<String>,
+
+    /// Most elements RFEngine puts in the DOM snapshot scripts see; the
+    /// rest of the document (in document order) is left out and a console
+    /// warning is emitted when the page loads. `0` disables the limit.
+    /// Default: 0.
+    pub max_dom_elements: usize,
+
+    /// Deepest nesting (counting `<html>` as 1) RFEngine puts in the DOM
+    /// snapshot scripts see; deeper subtrees are left out, with a console
+    /// warning when the page loads. `0` disables the limit. Default: 512.
+    pub max_dom_depth: usize,
+
+    /// Serialize DOM elements for scripts only when a query reaches them,
+    /// instead of the whole snapshot before every evaluation. Worthwhile on
+    /// large pages where scripts touch a few elements. RFEngine only; ignored
+    /// with `use_process_worker`. Default: false.
+    pub lazy_dom: bool,
+
+    /// Run the page's inline `<script>` elements, in document order, when a
+    /// page worker starts (`enable_js_isolation`), so globals they define
+    /// are there for later evaluations. External scripts are not fetched.
+    /// A script over the loop/recursion limits or `script_timeout_ms` is
+    /// skipped with a console warning. Needs `enable_js_isolation`
+    /// (`RFEngine::new` fails with `Error::ConfigError` otherwise).
+    /// RFEngine only. Default: false.
+    pub execute_inline_scripts: bool,
+
+    /// Fetch the page's `<script src>` resources and run them with the
+    /// inline ones when a page worker starts: parser-inserted scripts in
+    /// document order, then `defer`, then `async` scripts. Fetches go
+    /// through `on_request`, a short-lived cache and then the page fetch path
+    /// (resource loader, robots.txt, `max_html_bytes`, `record_dir`); a
+    /// non-2xx response skips the script. Needs
+    /// `enable_js_isolation`, like `execute_inline_scripts`. RFEngine only.
+    /// Default: false.
+    pub execute_external_scripts: bool,
+
+    /// Once a page worker has run the page's scripts and load events, run
+    /// the timers, microtasks and animation frames they scheduled before
+    /// `on_load` fires, advancing the logical clock to each pending timer
+    /// (at most `max_event_loop_steps` rounds, with a console warning when
+    /// timers are still pending then, within `script_timeout_ms`).
+    /// The title they set and the elements they change, add or remove show
+    /// up in text snapshots and later evaluations. Needs
+    /// `enable_js_isolation` (`RFEngine::new` fails with
+    /// `Error::ConfigError` otherwise). RFEngine only. Default: false.
+    pub run_until_idle_on_load: bool,
 }
 
 impl Default for EngineConfig {
@@ -146,6 +410,37 @@ impl Default for EngineConfig {
             enable_preconnect: true,
             // By default, wait for stylesheet fetches to complete on load.
             wait_for_stylesheets_on_load: true,
+            script_global_allowlist: None,
+            echo_console: false,
+            content_hash_html: false,
+            screenshot_on_error_dir: None,
+            cdp_eval_retries: 2,
+            json_result_mode: false,
+            strip_types: false,
+            random_seed: None,
+            max_event_loop_steps: 10000,
+            include_generated_content: false,
+            allow_file_urls: false,
+            respect_robots_txt: false,
+            per_host_delay_ms: 0,
+            worker_binary_path: None,
+            script_worker_pool_size: 0,
+            max_html_bytes: 0,
+            require_html: false,
+            default_charset: "utf-8".to_string(),
+            max_connections_per_host: 6,
+            stylesheet_phase_timeout_ms: 0,
+            max_stylesheet_bytes: 0,
+            probe_stylesheet_size: false,
+            offline_fixtures: None,
+            record_dir: None,
+            text_exclude_selectors: Vec::new(),
+            max_dom_elements: 0,
+            max_dom_depth: 512,
+            lazy_dom: false,
+            execute_inline_scripts: false,
+            execute_external_scripts: false,
+            run_until_idle_on_load: false,
         }
     }
 }
@@ -179,6 +474,66 @@ pub struct TextSnapshot {
     pub text: String,
     /// Final URL after redirects
     pub url: String,
+    /// Page language from `<html lang>`, or guessed from the text when the
+    /// `lang-detect` feature is enabled
+    pub detected_language: Option<String>,
+}
+
+impl TextSnapshot {
+    /// Number of words in `text`, splitting on Unicode whitespace
+    pub fn word_count(&self) -> usize {
+        self.text.split_whitespace().count()
+    }
+
+    /// Estimated reading time in minutes at `wpm` words per minute
+    /// (0.0 when `wpm` is 0)
+    pub fn reading_time_minutes(&self, wpm: u32) -> f64 {
+        if wpm == 0 {
+            return 0.0;
+        }
+        self.word_count() as f64 / wpm as f64
+    }
+
+    // Title and text with whitespace collapsed; the URL is left out so the
+    // same content served from different addresses hashes equal.
+    fn normalized_content(&self) -> String {
+        let collapse = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ");
+        format!("{}\n{}", collapse(&self.title), collapse(&self.text))
+    }
+
+    /// What changed from `previous` to this snapshot. Text is compared line
+    /// by line with whitespace collapsed, or word by word when either side
+    /// is a single line; the URL is ignored.
+    pub fn diff(&self, previous: &TextSnapshot) -> SnapshotDiff {
+        let collapse = |s: &str| s.split_whitespace().collect::<Vec<_>>().join(" ");
+        let (added_text, removed_text) = text_diff::diff_text(&previous.text, &self.text);
+        SnapshotDiff {
+            title_changed: collapse(&self.title) != collapse(&previous.title),
+            added_text,
+            removed_text,
+        }
+    }
+}
+
+/// Changes between two text snapshots
+///
+/// Returned by `Engine::diff_snapshot` and `TextSnapshot::diff` for change
+/// monitoring.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// Whether the page title differs
+    pub title_changed: bool,
+    /// Lines (or words) only in the newer snapshot
+    pub added_text: String,
+    /// Lines (or words) only in the older snapshot
+    pub removed_text: String,
+}
+
+impl SnapshotDiff {
+    /// True when neither the title nor the text changed
+    pub fn is_empty(&self) -> bool {
+        !self.title_changed && self.added_text.is_empty() && self.removed_text.is_empty()
+    }
 }
 
 /// Result of JavaScript execution
@@ -193,6 +548,41 @@ pub struct ScriptResult {
     pub is_error: bool,
 }
 
+/// Identifier returned by `Engine::add_init_script`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ScriptId(String);
+
+impl ScriptId {
+    #[cfg(any(feature = "rfengine", feature = "cdp"))]
+    pub(crate) fn new(id: impl Into<String>) -> Self {
+        Self(id.into())
+    }
+
+    /// Backend-specific identifier string
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for ScriptId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Progress signal emitted while `load_url` runs
+///
+/// RFEngine reports `fetch_started`, `body_received`, `stylesheets_fetched`
+/// and (when a page worker is used) `harness_initialized`; CDP reports
+/// `navigation_started`, `navigated` and `load_complete`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadProgress {
+    /// Name of the phase that just completed or started
+    pub phase: String,
+    /// Bytes received so far for this load (document plus subresources)
+    pub bytes_loaded: u64,
+}
+
 /// Console message emitted by the page
 #[derive(Debug, Clone)]
 pub struct ConsoleMessage {
@@ -208,6 +598,29 @@ pub struct ConsoleMessage {
     pub column: Option<u32>,
     /// Optional raw JS stack trace if provided by the engine
     pub stack: Option<String>,
+    /// Nesting depth of `console.group` when the message was emitted (0 when ungrouped).
+    /// `text` is already indented by two spaces per level.
+    pub group_depth: u32,
+    /// Wall-clock time the message was emitted, in milliseconds since the Unix epoch
+    pub timestamp_ms: u64,
+    /// All frames parsed from `stack`, top frame first (empty when unavailable).
+    /// `source`/`line`/`column` mirror the first entry. RFEngine only has them
+    /// when an `Error` carrying a `stack` is logged or thrown, since Boa does
+    /// not record call sites.
+    pub frames: Vec<StackFrame>,
+}
+
+/// A single frame parsed from a JavaScript stack trace
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    /// Function name if the engine reported one
+    pub function: Option<String>,
+    /// Script URL or filename
+    pub source: String,
+    /// 1-based line number
+    pub line: u32,
+    /// 1-based column number
+    pub column: u32,
 }
 
 /// Information about an outgoing network request
@@ -226,7 +639,7 @@ pub struct RequestInfo {
 }
 
 /// A cookie retrieved from the browser
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 pub struct Cookie {
     pub name: String,
     pub value: String,
@@ -251,6 +664,12 @@ pub struct CookieParam {
     pub http_only: Option<bool>,
     pub same_site: Option<String>,
     pub expires: Option<u64>,
+    /// Cookie priority: "Low", "Medium" or "High" (CDP only)
+    pub priority: Option<String>,
+    /// Top-level site of a partitioned (CHIPS) cookie, e.g.
+    /// `https://example.com`; partitioned cookies must also be `secure`
+    /// (CDP only)
+    pub partition_key: Option<String>,
 }
 
 /// Action to take when a request is observed by `on_request` handlers.
@@ -283,6 +702,14 @@ pub trait Engine {
     /// Load a URL and wait for the page to be ready
     fn load_url(&mut self, url: &str) -> Result<()>;
 
+    /// Make `html` the current page without a network fetch. Relative
+    /// resources resolve against `base_url`; without one the page has no
+    /// URL to resolve them against.
+    fn load_html(&mut self, html: &str, base_url: Option<&str>) -> Result<()> {
+        let _ = (html, base_url);
+        Err(Error::unsupported("load_html", backend_name::<Self>()))
+    }
+
     /// Render the current page as a text snapshot
     fn render_text_snapshot(&self) -> Result<TextSnapshot>;
 
@@ -300,10 +727,62 @@ pub trait Engine {
         self.evaluate_script(script)
     }
 
+    /// Like `evaluate_script`, but with a timeout for this call only instead
+    /// of `EngineConfig::script_timeout_ms`.
+    fn evaluate_script_timeout(&mut self, script: &str, timeout_ms: u64) -> Result<ScriptResult> {
+        let _ = (script, timeout_ms);
+        Err(Error::unsupported(
+            "evaluate_script_timeout",
+            backend_name::<Self>(),
+        ))
+    }
+
+    /// Evaluate `source` as an ES module, resolving `import`s relative to the
+    /// current page URL. The result is the module's `default` export.
+    fn evaluate_module(&mut self, source: &str) -> Result<ScriptResult> {
+        let _ = source;
+        Err(Error::unsupported(
+            "evaluate_module",
+            backend_name::<Self>(),
+        ))
+    }
+
+    /// Evaluate several scripts, returning one result per script in order.
+    /// Backends may batch them into a single round-trip; the default runs
+    /// them one by one.
+    fn evaluate_scripts(&mut self, scripts: &[&str]) -> Result<Vec<ScriptResult>> {
+        scripts.iter().map(|s| self.evaluate_script(s)).collect()
+    }
+
+    /// Evaluate JavaScript and return its completion value as JSON (via
+    /// `JSON.stringify`). Values without a JSON form, such as `undefined`,
+    /// become `null`.
+    fn evaluate_script_json(&mut self, script: &str) -> Result<serde_json::Value> {
+        let literal = serde_json::Value::from(script).to_string();
+        let res = self.evaluate_script(&format!("JSON.stringify(eval({}))", literal))?;
+        if res.is_error {
+            return Err(Error::ScriptError(res.value));
+        }
+        decode_json_result(&res.value)
+    }
+
+    /// Evaluate JavaScript and deserialize its result into `T`. Results that
+    /// do not match `T` are reported as `Error::ScriptError`.
+    fn evaluate_as<T>(&mut self, script: &str) -> Result<T>
+    where
+        Self: Sized,
+        T: serde::de::DeserializeOwned,
+    {
+        let value = self.evaluate_script_json(script)?;
+        serde_json::from_value(value)
+            .map_err(|e| Error::ScriptError(format!("Failed to deserialize script result: {}", e)))
+    }
+
     /// Register a callback to be invoked when a page finishes loading.
     /// The callback receives a `TextSnapshot` describing the loaded page.
     fn on_load<F>(&mut self, cb: F)
     where
+        Self: Sized,
         F: Fn(&TextSnapshot) + Send + Sync + 'static;
 
     /// Remove previously registered on_load callback if any
@@ -312,6 +791,7 @@ pub trait Engine {
     /// Register a callback for console messages emitted by the page.
     fn on_console<F>(&mut self, cb: F)
     where
+        Self: Sized,
         F: Fn(&ConsoleMessage) + Send + Sync + 'static;
 
     /// Remove previously registered on_console callback if any
@@ -323,11 +803,22 @@ pub trait Engine {
     /// fail, or be fulfilled with a custom response).
     fn on_request<F>(&mut self, cb: F)
     where
+        Self: Sized,
         F: Fn(&RequestInfo) -> RequestAction + Send + Sync + 'static;
 
     /// Remove previously registered on_request callback if any
     fn clear_on_request(&mut self);
 
+    /// Register a callback invoked synchronously with `LoadProgress` events
+    /// as `load_url` moves through its phases.
+    fn on_progress<F>(&mut self, cb: F)
+    where
+        Self: Sized,
+        F: Fn(LoadProgress) + Send + Sync + 'static;
+
+    /// Remove previously registered on_progress callback if any
+    fn clear_on_progress(&mut self);
+
     /// Get cookies relevant to the current page (returns cookie list)
     fn get_cookies(&self) -> Result<Vec<Cookie>>;
 
@@ -368,6 +859,8 @@ pub trait Engine {
             http_only: None,
             same_site: None,
             expires,
+            priority: None,
+            partition_key: None,
         };
         self.set_cookies(vec![param])
     }
@@ -390,8 +883,169 @@ pub trait Engine {
         Ok(())
     }
 
+    /// Cookies that would be sent with a request to `url`, applying domain,
+    /// path and `secure` matching. A domain with a leading `.` also matches
+    /// its subdomains.
+    fn get_cookies_for_url(&self, url: &str) -> Result<Vec<Cookie>> {
+        let _ = url;
+        Err(Error::unsupported(
+            "get_cookies_for_url",
+            backend_name::<Self>(),
+        ))
+    }
+
+    /// Remove cookies whose expiry time has passed, returning how many were
+    /// removed. Session cookies are kept.
+    fn purge_expired_cookies(&mut self) -> Result<usize> {
+        let now = cookies::unix_secs();
+        let expired: Vec<Cookie> = self
+            .get_cookies()?
+            .into_iter()
+            .filter(|c| cookies::is_expired(c, now))
+            .collect();
+        for c in &expired {
+            self.delete_cookie(&c.name, None, c.domain.as_deref(), c.path.as_deref())?;
+        }
+        Ok(expired.len())
+    }
+
+    /// Export the current cookies as a JSON array of `Cookie` objects.
+    fn export_cookies_json(&self) -> Result<String> {
+        cookies::to_json(&self.get_cookies()?)
+    }
+
+    /// Import cookies from the JSON produced by `export_cookies_json`.
+    fn import_cookies_json(&mut self, json: &str) -> Result<()> {
+        let parsed = cookies::from_json(json)?;
+        self.set_cookies(parsed.into_iter().map(cookies::to_param).collect())
+    }
+
+    /// Export the current cookies in Netscape `cookies.txt` format.
+    fn export_cookies_netscape(&self) -> Result<String> {
+        Ok(cookies::to_netscape(&self.get_cookies()?))
+    }
+
+    /// Import cookies from a Netscape `cookies.txt` file's contents.
+    fn import_cookies_netscape(&mut self, text: &str) -> Result<()> {
+        let parsed = cookies::from_netscape(text)?;
+        self.set_cookies(parsed.into_iter().map(cookies::to_param).collect())
+    }
+
+    /// Parse every `<script type="application/ld+json">` block on the current
+    /// page. Blocks that are not valid JSON are skipped.
+    fn extract_jsonld(&self) -> Result<Vec<serde_json::Value>> {
+        Err(Error::unsupported("extract_jsonld", backend_name::<Self>()))
+    }
+
+    /// Extract HTML microdata (`itemscope`/`itemtype`/`itemprop`, including
+    /// `itemref`) as `{"items": [{"type": [...], "properties": {...}}]}`.
+    fn extract_microdata(&self) -> Result<serde_json::Value> {
+        Err(Error::unsupported(
+            "extract_microdata",
+            backend_name::<Self>(),
+        ))
+    }
+
+    /// Convert the first table matching `selector` into records keyed by its
+    /// `<th>` header row (or `col0..colN` when there is no header). Repeated
+    /// headers are suffixed (`Name`, `Name_2`); an invalid selector returns
+    /// `Error::ConfigError`.
+    fn extract_table(&mut self, selector: &str) -> Result<Vec<HashMap<String, String>>> {
+        let _ = selector;
+        Err(Error::unsupported("extract_table", backend_name::<Self>()))
+    }
+
+    /// Absolute URL of the page after this one, for crawling paginated
+    /// listings: a `rel="next"` link, a link labelled "Next" (or `›`, `»`),
+    /// or the next number in numeric pagination. `None` on the last page.
+    fn find_next_page(&self) -> Result<Option<String>> {
+        Err(Error::unsupported("find_next_page", backend_name::<Self>()))
+    }
+
+    /// Absolute URLs of the RSS and Atom feeds the page links to with
+    /// `<link rel="alternate" type="application/rss+xml">` (or `atom+xml`).
+    fn extract_feeds(&self) -> Result<Vec<String>> {
+        Err(Error::unsupported("extract_feeds", backend_name::<Self>()))
+    }
+
+    /// Evaluate an XPath expression (a practical subset: `//tag`,
+    /// `[@attr='v']`, `[n]`, `text()`, `@attr`) and return the matched text or
+    /// attribute values.
+    fn query_xpath(&mut self, xpath: &str) -> Result<Vec<String>> {
+        let _ = xpath;
+        Err(Error::unsupported("query_xpath", backend_name::<Self>()))
+    }
+
+    /// Run a regular expression over the page text (as returned by
+    /// `render_text_snapshot`) and return every match, or the first capture
+    /// group when the pattern has one.
+    fn find_text(&self, pattern: &str) -> Result<Vec<String>> {
+        let re = regex::Regex::new(pattern)
+            .map_err(|e| Error::ConfigError(format!("Invalid pattern '{}': {}", pattern, e)))?;
+        let snapshot = self.render_text_snapshot()?;
+        Ok(re
+            .captures_iter(&snapshot.text)
+            .filter_map(|caps| caps.get(1).or_else(|| caps.get(0)))
+            .map(|m| m.as_str().to_string())
+            .collect())
+    }
+
+    /// Source of the current document: the body as received (RFEngine,
+    /// SimpleEngine), including non-HTML responses, or the serialized DOM
+    /// (CDP).
+    fn get_html(&self) -> Result<String> {
+        Err(Error::unsupported("get_html", backend_name::<Self>()))
+    }
+
+    /// Stable SHA-256 hex digest of the current page for change detection.
+    /// Hashes the normalized text snapshot (title and whitespace-collapsed
+    /// text), or the raw HTML when `EngineConfig::content_hash_html` is set on
+    /// backends that keep it.
+    fn content_hash(&self) -> Result<String> {
+        let snapshot = self.render_text_snapshot()?;
+        Ok(sha256_hex(snapshot.normalized_content().as_bytes()))
+    }
+
+    /// Register a script that runs on every new document before page
+    /// scripts (CDP) or right after the DOM harness (RFEngine), e.g. to stub
+    /// `navigator.webdriver` or seed globals.
+    fn add_init_script(&mut self, source: &str) -> Result<ScriptId> {
+        let _ = source;
+        Err(Error::unsupported(
+            "add_init_script",
+            backend_name::<Self>(),
+        ))
+    }
+
+    /// Unregister a script added with `add_init_script`.
+    fn remove_init_script(&mut self, id: ScriptId) -> Result<()> {
+        let _ = id;
+        Err(Error::unsupported(
+            "remove_init_script",
+            backend_name::<Self>(),
+        ))
+    }
+
+    /// Compare the current page with an earlier snapshot (for example one
+    /// taken before a reload), as [`TextSnapshot::diff`] does.
+    fn diff_snapshot(&self, previous: &TextSnapshot) -> Result<SnapshotDiff> {
+        Ok(self.render_text_snapshot()?.diff(previous))
+    }
+
+    /// Re-fetch the current URL and report whether its `content_hash`
+    /// changed. Backends that can fetch without navigating (RFEngine) leave
+    /// the loaded page untouched when nothing changed.
+    fn reload_if_changed(&mut self) -> Result<bool> {
+        let url = self.render_text_snapshot()?.url;
+        let previous = self.content_hash()?;
+        self.load_url(&url)?;
+        Ok(self.content_hash()? != previous)
+    }
+
     /// Close the engine and clean up resources
-    fn close(self) -> Result<()>;
+    fn close(self) -> Result<()>
+    where
+        Self: Sized;
 }
 
 /// Create a new engine instance with the default backend
@@ -417,10 +1071,90 @@ pub fn new_engine(config: EngineConfig) -> Result<impl Engine> {
     simple::SimpleEngine::new(config)
 }
 
+// Wall-clock milliseconds since the Unix epoch, used for event timestamps.
+#[cfg(any(feature = "rfengine", feature = "cdp"))]
+pub(crate) fn unix_millis() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+// Unqualified type name of an engine, for `Error::Unsupported`.
+fn backend_name<T: ?Sized>() -> &'static str {
+    let full = std::any::type_name::<T>();
+    full.rsplit("::").next().unwrap_or(full)
+}
+
+// Decode the value of a `JSON.stringify(...)` evaluation. CDP renders the
+// string as an escaped JSON string, Boa's display wraps it in quotes without
+// escaping, and `undefined` means the value had no JSON form.
+pub(crate) fn decode_json_result(value: &str) -> Result<serde_json::Value> {
+    let value = value.trim();
+    if value == "undefined" {
+        return Ok(serde_json::Value::Null);
+    }
+    let text = serde_json::from_str::<String>(value).unwrap_or_else(|_| {
+        value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value)
+            .to_string()
+    });
+    serde_json::from_str(&text)
+        .map_err(|e| Error::ScriptError(format!("Script result is not valid JSON: {}", e)))
+}
+
+// Whether a `Content-Type` value names an HTML or XML document, for
+// `EngineConfig::require_html`.
+#[cfg(feature = "rfengine")]
+pub(crate) fn is_html_content_type(content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or("")
+        .trim()
+        .to_ascii_lowercase();
+    essence == "text/html"
+        || essence == "text/xml"
+        || essence == "application/xml"
+        || essence.ends_with("+xml")
+}
+
+// Lowercase hex SHA-256 digest, used by `Engine::content_hash`.
+pub(crate) fn sha256_hex(data: &[u8]) -> String {
+    use sha2::{Digest, Sha256};
+    hex::encode(Sha256::digest(data))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_decode_json_result_handles_both_backends() {
+        let expected = serde_json::json!({"name": "x", "count": 3});
+        // CDP: escaped JSON string
+        assert_eq!(
+            decode_json_result(r#""{\"name\":\"x\",\"count\":3}""#).unwrap(),
+            expected
+        );
+        // Boa: quoted but unescaped
+        assert_eq!(
+            decode_json_result(r#""{"name":"x","count":3}""#).unwrap(),
+            expected
+        );
+        assert_eq!(decode_json_result("\"3\"").unwrap(), serde_json::json!(3));
+        assert_eq!(
+            decode_json_result("undefined").unwrap(),
+            serde_json::Value::Null
+        );
+        assert!(matches!(
+            decode_json_result("\"[object Object]\""),
+            Err(Error::ScriptError(_))
+        ));
+    }
+
     #[test]
     fn test_default_config() {
         let config = EngineConfig::default();
@@ -438,4 +1172,41 @@ mod tests {
         assert_eq!(viewport.width, 1920);
         assert_eq!(viewport.height, 1080);
     }
+
+    #[test]
+    fn test_text_snapshot_word_count_and_reading_time() {
+        let snapshot = TextSnapshot {
+            title: "T".into(),
+            // Mixed whitespace, including a non-breaking space and an ideographic space
+            text: "  Hello,\tworld!\nThis\u{00A0}is\u{3000}a   test.  ".into(),
+            url: "about:blank".into(),
+            detected_language: None,
+        };
+        assert_eq!(snapshot.word_count(), 6);
+        assert!((snapshot.reading_time_minutes(3) - 2.0).abs() < f64::EPSILON);
+        assert_eq!(snapshot.reading_time_minutes(0), 0.0);
+    }
+
+    #[test]
+    fn test_normalized_content_ignores_whitespace_and_url() {
+        let a = TextSnapshot {
+            title: "Status".into(),
+            text: "All systems\n  operational".into(),
+            url: "http://a/".into(),
+            detected_language: None,
+        };
+        let b = TextSnapshot {
+            text: " All systems operational ".into(),
+            url: "http://b/".into(),
+            ..a.clone()
+        };
+        let changed = TextSnapshot {
+            text: "Partial outage".into(),
+            ..a.clone()
+        };
+        let hash = |s: &TextSnapshot| sha256_hex(s.normalized_content().as_bytes());
+        assert_eq!(hash(&a), hash(&b));
+        assert_ne!(hash(&a), hash(&changed));
+        assert_eq!(hash(&a).len(), 64);
+    }
 }
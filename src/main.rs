@@ -124,6 +124,13 @@ fn worker_main() -> io::Result<()> {
         recursion_limit: usize,
     }
 
+    // `{"shutdown":true}` asks the worker to exit
+    #[derive(Deserialize)]
+    struct Control {
+        #[serde(default)]
+        shutdown: bool,
+    }
+
     #[derive(Serialize)]
     struct Res {
         id: u64,
@@ -142,6 +149,9 @@ fn worker_main() -> io::Result<()> {
         if line.trim().is_empty() {
             continue;
         }
+        if serde_json::from_str::<Control>(&line).is_ok_and(|c| c.shutdown) {
+            break;
+        }
         if let Ok(job) = serde_json::from_str::<Job>(&line) {
             // apply limits
             if job.loop_limit > 0 {
@@ -323,6 +333,8 @@ fn run_cli_cmd(run: Commands) -> Result<(), Box<dyn std::error::Error>> {
                         http_only: None,
                         same_site: None,
                         expires: None,
+                        priority: None,
+                        partition_key: None,
                     };
                     if let Err(e) = engine.set_cookies(vec![param]) {
                         eprintln!("Failed to set cookie: {}", e);
@@ -0,0 +1,359 @@
+//! Main-content extraction for RFEngine (feature: `readability`)
+//!
+//! A small take on Mozilla's Readability heuristics. Boilerplate (scripts,
+//! navigation, sidebars, comment sections) is dropped first; then every
+//! paragraph scores its parent and grandparent by length and comma count,
+//! candidates are weighted by class/id hints and penalized by link density,
+//! and the best one is returned together with sibling blocks that look like
+//! part of the same article.
+
+use scraper::{ElementRef, Html, Selector};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// The main content of a page, as returned by `RFEngine::extract_article`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Article {
+    /// Article headline (`og:title`, else `<title>` without a site suffix)
+    pub title: String,
+    /// Author line, from `<meta name="author">` or a byline element
+    pub byline: Option<String>,
+    /// Outer HTML of the content blocks, wrapped in a single `<div>`
+    pub content_html: String,
+    /// Text of the content, whitespace-collapsed with one blank line
+    /// between blocks
+    pub text: String,
+}
+
+// Paragraphs shorter than this don't score their ancestors
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+const DROPPED_TAGS: &[&str] = &[
+    "script", "style", "noscript", "template", "iframe", "form", "button", "nav", "aside",
+    "header", "footer", "svg", "object", "embed",
+];
+
+const UNLIKELY_HINTS: &[&str] = &[
+    "ad-",
+    "ads",
+    "advert",
+    "banner",
+    "breadcrumb",
+    "combx",
+    "comment",
+    "community",
+    "cookie",
+    "disqus",
+    "footer",
+    "menu",
+    "modal",
+    "nav",
+    "popup",
+    "promo",
+    "related",
+    "share",
+    "sidebar",
+    "social",
+    "sponsor",
+];
+
+const POSITIVE_HINTS: &[&str] = &[
+    "article", "blog", "body", "content", "entry", "main", "page", "post", "story", "text",
+];
+
+const BLOCK_TAGS: &[&str] = &[
+    "p",
+    "pre",
+    "blockquote",
+    "ul",
+    "ol",
+    "dl",
+    "table",
+    "figure",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+];
+
+static PARAGRAPH_SELECTOR: OnceLock<Selector> = OnceLock::new();
+
+fn paragraph_selector() -> &'static Selector {
+    PARAGRAPH_SELECTOR.get_or_init(|| Selector::parse("p, pre, td, blockquote").unwrap())
+}
+
+/// Extract the main content of `document`. Pages without any scoring
+/// paragraph fall back to the whole `<body>`.
+pub(crate) fn extract_article(document: &Html) -> Article {
+    let title = article_title(document);
+    let byline = byline(document);
+    let mut doc = document.clone();
+    drop_boilerplate(&mut doc);
+
+    let mut scores = HashMap::new();
+    for paragraph in doc.select(paragraph_selector()) {
+        let text = collapse_whitespace(&paragraph.text().collect::<String>());
+        let len = text.chars().count();
+        if len < MIN_PARAGRAPH_CHARS {
+            continue;
+        }
+        let score = 1.0 + text.matches(',').count() as f64 + (len / 100).min(3) as f64;
+        let ancestors = paragraph.ancestors().filter_map(ElementRef::wrap).take(2);
+        for (level, ancestor) in ancestors.enumerate() {
+            let base = scores
+                .entry(ancestor.id())
+                .or_insert_with(|| initial_score(&ancestor));
+            *base += if level == 0 { score } else { score / 2.0 };
+        }
+    }
+
+    let best = scores
+        .iter()
+        .filter_map(|(id, score)| {
+            let el = doc.tree.get(*id).and_then(ElementRef::wrap)?;
+            Some((el, score * (1.0 - link_density(&el))))
+        })
+        .max_by(|a, b| a.1.total_cmp(&b.1));
+
+    let blocks = match best {
+        Some((top, top_score)) => {
+            article_blocks(top, top_score, |el| scores.get(&el.id()).copied())
+        }
+        None => doc
+            .select(&Selector::parse("body").unwrap())
+            .next()
+            .into_iter()
+            .collect(),
+    };
+
+    let content_html = format!(
+        "<div>{}</div>",
+        blocks.iter().map(|b| b.html()).collect::<String>()
+    );
+    let text = blocks
+        .iter()
+        .flat_map(block_texts)
+        .filter(|t| !t.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Article {
+        title,
+        byline,
+        content_html,
+        text,
+    }
+}
+
+// The top candidate plus siblings that score close to it or read like
+// article paragraphs, in document order.
+fn article_blocks<'a>(
+    top: ElementRef<'a>,
+    top_score: f64,
+    score_of: impl Fn(&ElementRef) -> Option<f64>,
+) -> Vec<ElementRef<'a>> {
+    let Some(parent) = top.parent() else {
+        return vec![top];
+    };
+    let threshold = (top_score * 0.2).max(10.0);
+    parent
+        .children()
+        .filter_map(ElementRef::wrap)
+        .filter(|sibling| {
+            if sibling.id() == top.id() {
+                return true;
+            }
+            if score_of(sibling).is_some_and(|s| s * (1.0 - link_density(sibling)) >= threshold) {
+                return true;
+            }
+            if sibling.value().name() != "p" {
+                return false;
+            }
+            let text = collapse_whitespace(&sibling.text().collect::<String>());
+            let density = link_density(sibling);
+            (text.len() > 80 && density < 0.25)
+                || (!text.is_empty() && density == 0.0 && text.ends_with('.'))
+        })
+        .collect()
+}
+
+// Text of each block-level piece of `el` so paragraphs stay separated
+fn block_texts(el: &ElementRef) -> Vec<String> {
+    let name = el.value().name();
+    if BLOCK_TAGS.contains(&name)
+        || !el
+            .children()
+            .filter_map(ElementRef::wrap)
+            .any(|c| is_block(&c))
+    {
+        return vec![collapse_whitespace(&el.text().collect::<String>())];
+    }
+    let mut out = Vec::new();
+    let mut inline = String::new();
+    for child in el.children() {
+        match ElementRef::wrap(child) {
+            Some(child_el) if is_block(&child_el) => {
+                out.push(collapse_whitespace(&std::mem::take(&mut inline)));
+                out.extend(block_texts(&child_el));
+            }
+            Some(child_el) => inline.extend(child_el.text()),
+            None => {
+                if let Some(text) = child.value().as_text() {
+                    inline.push_str(text);
+                }
+            }
+        }
+    }
+    out.push(collapse_whitespace(&inline));
+    out
+}
+
+fn is_block(el: &ElementRef) -> bool {
+    let name = el.value().name();
+    BLOCK_TAGS.contains(&name) || matches!(name, "div" | "section" | "article" | "main" | "h1")
+}
+
+// Remove elements that are never article content
+fn drop_boilerplate(doc: &mut Html) {
+    let dropped: Vec<_> = doc
+        .root_element()
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter(|el| {
+            let name = el.value().name();
+            if DROPPED_TAGS.contains(&name) || el.value().attr("hidden").is_some() {
+                return true;
+            }
+            if matches!(name, "body" | "html" | "article" | "main") {
+                return false;
+            }
+            let hints = class_and_id(el);
+            UNLIKELY_HINTS.iter().any(|h| hints.contains(h))
+                && !POSITIVE_HINTS.iter().any(|h| hints.contains(h))
+        })
+        .map(|el| el.id())
+        .collect();
+    for id in dropped {
+        if let Some(mut node) = doc.tree.get_mut(id) {
+            node.detach();
+        }
+    }
+}
+
+fn initial_score(el: &ElementRef) -> f64 {
+    let tag = match el.value().name() {
+        "article" => 10.0,
+        "div" | "main" | "section" => 5.0,
+        "pre" | "td" | "blockquote" => 3.0,
+        "address" | "ol" | "ul" | "dl" | "dd" | "dt" | "li" => -3.0,
+        "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+        _ => 0.0,
+    };
+    tag + class_weight(el)
+}
+
+// +25 for content-like class/id hints, -25 for boilerplate ones
+fn class_weight(el: &ElementRef) -> f64 {
+    let hints = class_and_id(el);
+    let mut weight = 0.0;
+    if POSITIVE_HINTS.iter().any(|h| hints.contains(h)) {
+        weight += 25.0;
+    }
+    if UNLIKELY_HINTS.iter().any(|h| hints.contains(h)) {
+        weight -= 25.0;
+    }
+    weight
+}
+
+fn class_and_id(el: &ElementRef) -> String {
+    let attr = |name| el.value().attr(name).unwrap_or_default();
+    format!("{} {}", attr("class"), attr("id")).to_ascii_lowercase()
+}
+
+// Share of `el`'s text that sits inside links
+fn link_density(el: &ElementRef) -> f64 {
+    let total: usize = el.text().map(|t| t.trim().len()).sum();
+    if total == 0 {
+        return 0.0;
+    }
+    let linked: usize = el
+        .descendants()
+        .filter_map(ElementRef::wrap)
+        .filter(|e| e.value().name() == "a")
+        .flat_map(|a| a.text())
+        .map(|t| t.trim().len())
+        .sum();
+    linked as f64 / total as f64
+}
+
+fn article_title(document: &Html) -> String {
+    let meta = Selector::parse("meta[property=\"og:title\"]").unwrap();
+    if let Some(title) = document
+        .select(&meta)
+        .find_map(|m| m.value().attr("content"))
+        .map(collapse_whitespace)
+        .filter(|t| !t.is_empty())
+    {
+        return title;
+    }
+    let title = document
+        .select(&Selector::parse("title").unwrap())
+        .next()
+        .map(|t| collapse_whitespace(&t.text().collect::<String>()))
+        .unwrap_or_default();
+    // "Headline | Site": keep the headline when it stands on its own
+    for separator in [" | ", " - ", " – ", " — ", " :: "] {
+        if let Some((head, _)) = title.rsplit_once(separator) {
+            if head.split_whitespace().count() >= 3 {
+                return head.to_string();
+            }
+        }
+    }
+    title
+}
+
+fn byline(document: &Html) -> Option<String> {
+    let meta = Selector::parse("meta[name=\"author\"]").unwrap();
+    let from_meta = document
+        .select(&meta)
+        .find_map(|m| m.value().attr("content"))
+        .map(collapse_whitespace);
+    let byline = Selector::parse(
+        "[rel=\"author\"], [itemprop=\"author\"], .byline, .author, #byline, #author",
+    )
+    .unwrap();
+    from_meta
+        .into_iter()
+        .chain(
+            document
+                .select(&byline)
+                .map(|el| collapse_whitespace(&el.text().collect::<String>())),
+        )
+        .find(|b| !b.is_empty() && b.chars().count() < 100)
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_link_heavy_blocks_lose_to_prose() {
+        let html = r#"<html><head><title>Why Rust Feels Fast | Example Blog</title></head><body>
+            <div class="links"><p><a href="/a">A long list of links that should not win, really</a>, <a href="/b">and another one</a></p></div>
+            <div id="story">
+              <p>Rust programs tend to be fast because the compiler does a lot of work up front, which leaves little for the runtime.</p>
+              <p>Zero-cost abstractions, monomorphization and the lack of a garbage collector all help, in different ways.</p>
+            </div>
+          </body></html>"#;
+        let article = extract_article(&Html::parse_document(html));
+        assert_eq!(article.title, "Why Rust Feels Fast");
+        assert_eq!(article.byline, None);
+        assert!(article.content_html.contains("Zero-cost abstractions"));
+        assert!(!article.text.contains("list of links"));
+        assert_eq!(article.text.split("\n\n").count(), 2);
+    }
+}
@@ -2,8 +2,78 @@
 /// This file was made entirely by AI as I suck as javascript
 /// So please be kind when reviewing it :)
 
+// Everything the harness defines is private to this function. The public API
+// (all of it, or only what `script_global_allowlist` lists) and the entry
+// points the engine calls are installed on the global object at the end.
+(function() {
+
+// Public names `script_global_allowlist` lets scripts see, or null for all
+var __rfox_global_allowlist = (typeof __RFOX_GLOBAL_ALLOWLIST__ !== 'undefined' ? __RFOX_GLOBAL_ALLOWLIST__ : null);
+function __rfox_global_allowed(name) {
+    return __rfox_global_allowlist === null || __rfox_global_allowlist.indexOf(name) !== -1;
+}
+
 var __rfox_dom = (typeof __RFOX_ELEMENTS__ !== 'undefined' ? __RFOX_ELEMENTS__ : []);
 
+// `lazy_dom`: the engine parses the page source natively and elements are
+// fetched one by one the first time something reaches them. The source is
+// substituted here only, so the page is inlined once.
+var __rfox_lazy_source = __RFOX_LAZY_DOM__;
+var __rfox_lazy_dom = false;
+if (typeof __rfox_lazy_source === 'string' && typeof __rfox_dom_open === 'function') {
+    __rfox_dom = new Array(__rfox_dom_open(__rfox_lazy_source, __RFOX_MAX_DOM_ELEMENTS__, __RFOX_MAX_DOM_DEPTH__));
+    __rfox_lazy_dom = true;
+}
+// Elements from here on were added by scripts (see `__rfox_dom_changes`)
+var __rfox_snapshot_len = __rfox_dom.length;
+
+// Element `i` of the snapshot, fetching it from the engine first if needed
+function __rfox_node(i) {
+    var el = __rfox_dom[i];
+    if (el === undefined && __rfox_lazy_dom && i >= 0 && i < __rfox_dom.length) {
+        var json = __rfox_dom_node(i);
+        el = __rfox_dom[i] = (json === null ? null : JSON.parse(json));
+    }
+    return el;
+}
+
+// Every snapshot element, fetching the ones not seen yet
+function __rfox_all_nodes() {
+    for (var i=0;i<__rfox_dom.length;i++) __rfox_node(i);
+    return __rfox_dom;
+}
+
+// Indices of the elements matching any of the comma-separated selectors in
+// `sel`, in document order (only the first when `first` is set), limited to
+// descendants of element `within` when given. Lazy elements nothing has
+// fetched yet are matched natively; fetched ones are matched here so scripts'
+// changes to them are seen.
+function __rfox_select(sel, first, within) {
+    var parts = sel.split(',').map(function(s){return s.trim();});
+    var native = null;
+    if (__rfox_lazy_dom) {
+        native = {};
+        var hits = JSON.parse(__rfox_dom_select(sel));
+        for (var h=0; h<hits.length; h++) native[hits[h]] = true;
+    }
+    var out = [];
+    for (var i=0;i<__rfox_dom.length;i++) {
+        var el = __rfox_dom[i];
+        var hit = false;
+        if (el === undefined) {
+            hit = native !== null && native[i] === true;
+        } else if (el !== null) {
+            for (var pi=0; pi<parts.length && !hit; pi++) hit = __matches(el, parts[pi]);
+        }
+        if (hit && within !== undefined && !__rfox_is_descendant(i, within)) hit = false;
+        if (hit) {
+            out.push(i);
+            if (first) break;
+        }
+    }
+    return out;
+}
+
 function __matches_simple(el, sel) {
     if (!sel) return false;
     if (sel[0] === '#') return el.id === sel.slice(1);
@@ -37,8 +107,9 @@ function __matches_simple(el, sel) {
         var idx = __rfox_dom.indexOf(el);
         if (idx === -1) { return false; }
         var parent = el.parent;
+        if (pcname === 'root' && parent !== null && parent !== undefined) return false;
         var siblings = [];
-        for (var si=0; si<__rfox_dom.length; si++) { if (__rfox_dom[si].parent === parent) siblings.push(si); }
+        for (var si=0; si<__rfox_dom.length; si++) { var sn = __rfox_node(si); if (sn && sn.parent === parent) siblings.push(si); }
         if (pcname === 'first-child') {
             if (siblings.length === 0) return false;
             if (siblings[0] !== idx) return false;
@@ -48,6 +119,7 @@ function __matches_simple(el, sel) {
             if (siblings[siblings.length-1] !== idx) return false;
         }
     }
+    if (sel === '') return pcname !== null;
     var parts = sel.split('.');
     if (parts.length === 2) return el.tag.toLowerCase() === parts[0].toLowerCase() && (el.class||'').split(/\s+/).indexOf(parts[1]) !== -1;
     return el.tag.toLowerCase() === sel.toLowerCase();
@@ -71,7 +143,7 @@ function __matches(el, selector) {
             var sel = tokens[i];
             var pidx = cur.parent;
             if (pidx === null || pidx === undefined) return false;
-            var parent = __rfox_dom[pidx];
+            var parent = __rfox_node(pidx);
             if (!__matches_simple(parent, sel)) return false;
             cur = parent;
             i--;
@@ -84,7 +156,7 @@ function __matches(el, selector) {
             // Right-most token may refer to the element itself
             if (i === tokens.length - 1 && __matches_simple(cur, sel)) { i--; continue; }
             while (pidx !== null && pidx !== undefined) {
-                var ancestor = __rfox_dom[pidx];
+                var ancestor = __rfox_node(pidx);
                 if (__matches_simple(ancestor, sel)) { found = true; cur = ancestor; break; }
                 pidx = ancestor.parent;
             }
@@ -96,31 +168,188 @@ function __matches(el, selector) {
     return true;
 }
 
-function querySelector(sel) {
-    var parts = sel.split(',').map(function(s){return s.trim();});
-    for (var i=0;i<__rfox_dom.length;i++) {
-        for (var pi=0; pi<parts.length; pi++) {
-            if (__matches(__rfox_dom[i], parts[pi])) return __wrap_el(__rfox_dom[i]);
+// Parent element of `el`: its snapshot parent, or the detached element it
+// was appended to
+function __rfox_parent_of(el) {
+    if (el.__rfox_owner) return el.__rfox_owner;
+    if (el.parent === null || el.parent === undefined || __rfox_dom.indexOf(el) === -1) return null;
+    return __rfox_node(el.parent);
+}
+
+// Whether element `i` sits below element `ancestor`
+function __rfox_is_descendant(i, ancestor) {
+    var node = __rfox_node(i);
+    while (node && node.parent !== null && node.parent !== undefined) {
+        if (node.parent === ancestor) return true;
+        node = __rfox_node(node.parent);
+    }
+    return false;
+}
+
+var __rfox_void_tags = { area: 1, base: 1, br: 1, col: 1, embed: 1, hr: 1, img: 1, input: 1, link: 1, meta: 1, source: 1, track: 1, wbr: 1 };
+
+function __rfox_decode_entities(s) {
+    return s.replace(/&(#x[0-9a-fA-F]+|#[0-9]+|amp|lt|gt|quot|apos|nbsp);/g, function(m, e) {
+        if (e[0] === '#') return String.fromCharCode(e[1] === 'x' || e[1] === 'X' ? parseInt(e.slice(2), 16) : parseInt(e.slice(1), 10));
+        return { amp: '&', lt: '<', gt: '>', quot: '"', apos: "'", nbsp: '\u00a0' }[e];
+    });
+}
+
+// Parse an HTML fragment into snapshot-shaped elements in document order;
+// `parent` indexes into the returned list (null for top-level elements).
+// Unclosed tags are closed at the end and stray end tags are ignored.
+function __rfox_parse_fragment(markup) {
+    var nodes = [];
+    var open = [];
+    var text = '';
+    var token = /<!--[\s\S]*?-->|<([a-zA-Z][^\s\/>]*)((?:\s+[^\s"'>\/=]+(?:\s*=\s*(?:"[^"]*"|'[^']*'|[^\s"'=<>`]+))?)*)\s*(\/?)>|<\/([a-zA-Z][^\s\/>]*)\s*>|[^<]+|</g;
+    var attrRe = /([^\s"'>\/=]+)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'=<>`]+)))?/g;
+    function addText(t) {
+        text += t;
+        for (var k=0;k<open.length;k++) nodes[open[k]].text += t;
+    }
+    var m;
+    while ((m = token.exec(markup)) !== null) {
+        if (m[1]) {
+            var tag = m[1].toLowerCase();
+            var el = { tag: tag, id: '', class: '', text: '', attributes: [], parent: open.length ? open[open.length-1] : null };
+            var a;
+            attrRe.lastIndex = 0;
+            while ((a = attrRe.exec(m[2] || '')) !== null) {
+                var name = a[1].toLowerCase();
+                var value = __rfox_decode_entities(a[2] !== undefined ? a[2] : a[3] !== undefined ? a[3] : a[4] !== undefined ? a[4] : '');
+                el.attributes.push([name, value]);
+                if (name === 'id') el.id = value;
+                if (name === 'class') el.class = value;
+            }
+            nodes.push(el);
+            if (tag === 'script' || tag === 'style' || tag === 'textarea' || tag === 'title') {
+                // Raw text up to the matching end tag
+                var end = markup.toLowerCase().indexOf('</' + tag, token.lastIndex);
+                if (end === -1) end = markup.length;
+                el.text = markup.slice(token.lastIndex, end);
+                for (var k=0;k<open.length;k++) nodes[open[k]].text += el.text;
+                text += el.text;
+                token.lastIndex = end;
+            } else if (!m[3] && !__rfox_void_tags[tag]) {
+                open.push(nodes.length - 1);
+            }
+        } else if (m[4]) {
+            var closing = m[4].toLowerCase();
+            for (var j=open.length-1;j>=0;j--) {
+                if (nodes[open[j]].tag === closing) { open.length = j; break; }
+            }
+        } else if (m[0].indexOf('<!--') !== 0) {
+            addText(__rfox_decode_entities(m[0]));
         }
     }
-    return __wrap_el(null);
+    return { nodes: nodes, text: text };
 }
 
-function querySelectorAll(sel) {
+// Element `el`'s children: snapshot indices when `el` is in the snapshot,
+// otherwise the elements collected on it while detached
+function __rfox_children_of(el) {
+    var idx = __rfox_dom.indexOf(el);
+    if (idx === -1) return (el.__rfox_children || []).slice();
     var out = [];
-    var parts = sel.split(',').map(function(s){return s.trim();});
-    for (var i=0;i<__rfox_dom.length;i++) {
-        for (var pi=0; pi<parts.length; pi++) {
-            if (__matches(__rfox_dom[i], parts[pi])) out.push(__wrap_el(__rfox_dom[i]));
-        }
+    for (var i=idx+1;i<__rfox_dom.length;i++) {
+        var node = __rfox_node(i);
+        if (node && node.parent === idx) out.push(node);
     }
     return out;
 }
 
+// Take element `el` out of its parent. Elements removed from the snapshot
+// keep their subtree in `__rfox_children` so they can be attached again.
+function __rfox_detach(el) {
+    if (el.__rfox_owner) {
+        var kids = el.__rfox_owner.__rfox_children;
+        kids.splice(kids.indexOf(el), 1);
+        el.__rfox_owner = null;
+        return;
+    }
+    var idx = __rfox_dom.indexOf(el);
+    if (idx === -1) return;
+    // Parents come before their children, so one pass finds the whole subtree
+    var removed = {};
+    removed[idx] = el;
+    __rfox_dom[idx] = null;
+    for (var i=idx+1;i<__rfox_dom.length;i++) {
+        var node = __rfox_node(i);
+        if (node && removed[node.parent]) {
+            var owner = removed[node.parent];
+            (owner.__rfox_children = owner.__rfox_children || []).push(node);
+            node.__rfox_owner = owner;
+            removed[i] = node;
+            __rfox_dom[i] = null;
+        }
+    }
+    el.parent = null;
+}
+
+// Add detached element `child` as the last child of `parent`. Under a
+// snapshot element it is appended to `__rfox_dom` with its subtree.
+function __rfox_append(parent, child) {
+    var pidx = __rfox_dom.indexOf(parent);
+    if (pidx === -1) {
+        (parent.__rfox_children = parent.__rfox_children || []).push(child);
+        child.__rfox_owner = parent;
+        return;
+    }
+    var kids = child.__rfox_children || [];
+    child.__rfox_children = null;
+    child.__rfox_owner = null;
+    child.parent = pidx;
+    __rfox_dom.push(child);
+    for (var k=0;k<kids.length;k++) {
+        kids[k].__rfox_owner = null;
+        __rfox_append(child, kids[k]);
+    }
+}
+
+// Replace the children of `el` with the elements parsed from `markup`, so
+// queries (including `el.querySelector`) see them
+function __rfox_set_inner_html(el, markup) {
+    markup = String(markup);
+    var fragment = __rfox_parse_fragment(markup);
+    __rfox_children_of(el).forEach(__rfox_detach);
+    var nodes = fragment.nodes;
+    // Build the fragment's subtrees, then attach its top-level elements
+    var top = [];
+    for (var n=nodes.length-1;n>=0;n--) {
+        var parent = nodes[n].parent;
+        nodes[n].parent = null;
+        if (parent === null) {
+            top.unshift(nodes[n]);
+        } else {
+            var owner = nodes[parent];
+            (owner.__rfox_children = owner.__rfox_children || []).unshift(nodes[n]);
+            nodes[n].__rfox_owner = owner;
+        }
+    }
+    for (var m=0;m<nodes.length;m++) nodes[m].__rfox_from_markup = true;
+    for (var t=0;t<top.length;t++) __rfox_append(el, top[t]);
+    el.__rfox_content = markup;
+    el.html = markup;
+    el.text = fragment.text;
+}
+
+function querySelector(sel) {
+    var hits = __rfox_select(sel, true);
+    return __wrap_el(hits.length ? __rfox_node(hits[0]) : null);
+}
+
+function querySelectorAll(sel) {
+    return __rfox_select(sel, false).map(function(i){ return __wrap_el(__rfox_node(i)); });
+}
+
+// Element tags whose URL attribute is exposed as a resolved property
+var __rfox_url_attrs = { a: 'href', area: 'href', link: 'href', img: 'src', script: 'src', iframe: 'src', source: 'src' };
+
 // Wrap element with safe helpers to avoid TypeErrors.
 function __wrap_el(el) {
     if (!el) {
-        return { text: "", id: "", class: "", tag: "", attributes: [], getAttribute: function() { return null; }, textContent: function() { return ""; }, innerHTML: function(v) { if (arguments.length) { this.text = v; } return (this.text === undefined || this.text === null) ? "" : this.text; } };
+        return { text: "", id: "", class: "", tag: "", attributes: [], getAttribute: function() { return null; }, textContent: function() { return ""; }, innerHTML: function(v) { if (arguments.length) { this.html = String(v); } return this.html !== undefined ? this.html : ""; } };
     }
     if (!el.getAttribute) {
         el.getAttribute = function(n) {
@@ -130,6 +359,9 @@ function __wrap_el(el) {
     }
     if (!el.setAttribute) {
         el.setAttribute = function(n, v) {
+            this.__rfox_attrs_changed = true;
+            if (n === 'id') this.id = String(v);
+            if (n === 'class') this.class = String(v);
             for (var i=0;i<this.attributes.length;i++) { if (this.attributes[i][0] === n) { this.attributes[i][1] = String(v); return; } }
             this.attributes.push([n, String(v)]);
             // keep dataset in sync if data-* attribute
@@ -140,10 +372,46 @@ function __wrap_el(el) {
         };
     }
     if (!el.textContent) {
-        el.textContent = function() { return (this.text === undefined || this.text === null) ? "" : this.text; };
+        el.textContent = function(v) {
+            if (arguments.length) {
+                __rfox_children_of(this).forEach(__rfox_detach);
+                this.text = String(v);
+                this.__rfox_content = __rfox_escape_html(this.text);
+                delete this.html;
+            }
+            return (this.text === undefined || this.text === null) ? "" : this.text;
+        };
+    }
+    if (!el.appendChild) {
+        el.appendChild = function(child) {
+            __rfox_detach(child);
+            __rfox_append(this, __wrap_el(child));
+            delete this.html;
+            return child;
+        };
+    }
+    if (!el.removeChild) {
+        el.removeChild = function(child) {
+            if (__rfox_children_of(this).indexOf(child) === -1) throw new Error('NotFoundError: not a child of this element');
+            __rfox_detach(child);
+            delete this.html;
+            return child;
+        };
     }
     if (!el.innerHTML) {
-        el.innerHTML = function(v) { if (arguments.length) { this.text = v; } return (this.text === undefined || this.text === null) ? "" : this.text; };
+        el.innerHTML = function(v) { if (arguments.length) { __rfox_set_inner_html(this, v); } if (this.html !== undefined) return this.html; return (this.text === undefined || this.text === null) ? "" : this.text; };
+    }
+    // `href`/`src` properties resolve the attribute against `document.baseURI`
+    var urlAttr = __rfox_url_attrs[String(el.tag || '').toLowerCase()];
+    if (urlAttr && !(urlAttr in el)) {
+        Object.defineProperty(el, urlAttr, {
+            get: function() {
+                var v = this.getAttribute(urlAttr);
+                if (v === null) return '';
+                try { return new URL(v, document.baseURI).href; } catch (e) { return v; }
+            },
+            configurable: true
+        });
     }
     // dataset: expose data-* attributes as camelCase props and helpers
     if (!el.dataset) {
@@ -162,11 +430,22 @@ function __wrap_el(el) {
             };
         })(el);
     }
+    if (!el.addEventListener) {
+        el.addEventListener = function(type, fn, options) { __rfox_add_listener(this, type, fn, options); };
+        el.removeEventListener = function(type, fn, options) { __rfox_remove_listener(this, type, fn, options); };
+        el.dispatchEvent = function(event) { return __rfox_dispatch(this, event); };
+    }
+    if (!('parentElement' in el)) {
+        Object.defineProperty(el, 'parentElement', {
+            get: function() { var p = __rfox_parent_of(this); return p ? __wrap_el(p) : null; },
+            configurable: true
+        });
+    }
     if (!el.querySelector) {
-        el.querySelector = function(sel) { for (var i=0;i<__rfox_dom.length;i++) { if (__matches(__rfox_dom[i], sel)) return __wrap_el(__rfox_dom[i]); } return __wrap_el(null); };
+        el.querySelector = function(sel) { var hits = __rfox_select(sel, true, __rfox_dom.indexOf(this)); return __wrap_el(hits.length ? __rfox_node(hits[0]) : null); };
     }
     if (!el.querySelectorAll) {
-        el.querySelectorAll = function(sel) { var out=[]; for (var i=0;i<__rfox_dom.length;i++) { if (__matches(__rfox_dom[i], sel)) out.push(__wrap_el(__rfox_dom[i])); } return out; };
+        el.querySelectorAll = function(sel) { return __rfox_select(sel, false, __rfox_dom.indexOf(this)).map(function(i){ return __wrap_el(__rfox_node(i)); }); };
     }
     return el;
 }
@@ -175,7 +454,7 @@ function __wrap_el(el) {
 // Snapshot helper useful for debugging and tests
 function __rfox_snapshot() {
     try {
-        return JSON.stringify({ dom: __rfox_dom, styles: __rfox_styles, now: __rfox_now, macrotasks: __rfox_macrotasks.map(function(m){ return { id: m.id, due: m.due, interval: m.interval }; }) });
+        return JSON.stringify({ dom: __rfox_all_nodes(), styles: __rfox_styles, now: __rfox_now, macrotasks: __rfox_macrotasks.map(function(m){ return { id: m.id, due: m.due, interval: m.interval }; }) });
     } catch(e) { return ''; }
 }
 
@@ -210,63 +489,114 @@ var __rfox_rules = [];
 })();
 
 // Normalization helpers for computed styles (extended)
-function normalizeColor(val) {
-    if (!val) return val;
+// CSS named colors (CSS Color Module Level 4)
+var __rfox_named_colors = {
+    aliceblue:'f0f8ff',antiquewhite:'faebd7',aqua:'00ffff',aquamarine:'7fffd4',azure:'f0ffff',beige:'f5f5dc',
+    bisque:'ffe4c4',black:'000000',blanchedalmond:'ffebcd',blue:'0000ff',blueviolet:'8a2be2',brown:'a52a2a',
+    burlywood:'deb887',cadetblue:'5f9ea0',chartreuse:'7fff00',chocolate:'d2691e',coral:'ff7f50',
+    cornflowerblue:'6495ed',cornsilk:'fff8dc',crimson:'dc143c',cyan:'00ffff',darkblue:'00008b',
+    darkcyan:'008b8b',darkgoldenrod:'b8860b',darkgray:'a9a9a9',darkgreen:'006400',darkgrey:'a9a9a9',
+    darkkhaki:'bdb76b',darkmagenta:'8b008b',darkolivegreen:'556b2f',darkorange:'ff8c00',darkorchid:'9932cc',
+    darkred:'8b0000',darksalmon:'e9967a',darkseagreen:'8fbc8f',darkslateblue:'483d8b',darkslategray:'2f4f4f',
+    darkslategrey:'2f4f4f',darkturquoise:'00ced1',darkviolet:'9400d3',deeppink:'ff1493',deepskyblue:'00bfff',
+    dimgray:'696969',dimgrey:'696969',dodgerblue:'1e90ff',firebrick:'b22222',floralwhite:'fffaf0',
+    forestgreen:'228b22',fuchsia:'ff00ff',gainsboro:'dcdcdc',ghostwhite:'f8f8ff',gold:'ffd700',
+    goldenrod:'daa520',gray:'808080',green:'008000',greenyellow:'adff2f',grey:'808080',honeydew:'f0fff0',
+    hotpink:'ff69b4',indianred:'cd5c5c',indigo:'4b0082',ivory:'fffff0',khaki:'f0e68c',lavender:'e6e6fa',
+    lavenderblush:'fff0f5',lawngreen:'7cfc00',lemonchiffon:'fffacd',lightblue:'add8e6',lightcoral:'f08080',
+    lightcyan:'e0ffff',lightgoldenrodyellow:'fafad2',lightgray:'d3d3d3',lightgreen:'90ee90',
+    lightgrey:'d3d3d3',lightpink:'ffb6c1',lightsalmon:'ffa07a',lightseagreen:'20b2aa',lightskyblue:'87cefa',
+    lightslategray:'778899',lightslategrey:'778899',lightsteelblue:'b0c4de',lightyellow:'ffffe0',
+    lime:'00ff00',limegreen:'32cd32',linen:'faf0e6',magenta:'ff00ff',maroon:'800000',
+    mediumaquamarine:'66cdaa',mediumblue:'0000cd',mediumorchid:'ba55d3',mediumpurple:'9370db',
+    mediumseagreen:'3cb371',mediumslateblue:'7b68ee',mediumspringgreen:'00fa9a',mediumturquoise:'48d1cc',
+    mediumvioletred:'c71585',midnightblue:'191970',mintcream:'f5fffa',mistyrose:'ffe4e1',moccasin:'ffe4b5',
+    navajowhite:'ffdead',navy:'000080',oldlace:'fdf5e6',olive:'808000',olivedrab:'6b8e23',orange:'ffa500',
+    orangered:'ff4500',orchid:'da70d6',palegoldenrod:'eee8aa',palegreen:'98fb98',paleturquoise:'afeeee',
+    palevioletred:'db7093',papayawhip:'ffefd5',peachpuff:'ffdab9',peru:'cd853f',pink:'ffc0cb',plum:'dda0dd',
+    powderblue:'b0e0e6',purple:'800080',rebeccapurple:'663399',red:'ff0000',rosybrown:'bc8f8f',
+    royalblue:'4169e1',saddlebrown:'8b4513',salmon:'fa8072',sandybrown:'f4a460',seagreen:'2e8b57',
+    seashell:'fff5ee',sienna:'a0522d',silver:'c0c0c0',skyblue:'87ceeb',slateblue:'6a5acd',slategray:'708090',
+    slategrey:'708090',snow:'fffafa',springgreen:'00ff7f',steelblue:'4682b4',tan:'d2b48c',teal:'008080',
+    thistle:'d8bfd8',tomato:'ff6347',turquoise:'40e0d0',violet:'ee82ee',wheat:'f5deb3',white:'ffffff',
+    whitesmoke:'f5f5f5',yellow:'ffff00',yellowgreen:'9acd32'
+};
+
+// Parse a CSS color into [r, g, b, a] (channels 0-255, alpha 0-1), or null.
+// Accepts hex (#rgb, #rgba, #rrggbb, #rrggbbaa), rgb()/rgba() and hsl()/hsla()
+// with comma or space syntax (optional "/ alpha"), named colors and transparent.
+function __rfox_parse_color(val) {
     val = String(val).trim().toLowerCase();
-    var hexm = val.match(/^#([0-9a-f]{3}|[0-9a-f]{6})$/);
+    if (val === 'transparent') return [0, 0, 0, 0];
+    if (Object.prototype.hasOwnProperty.call(__rfox_named_colors, val)) val = '#' + __rfox_named_colors[val];
+    var hexm = val.match(/^#([0-9a-f]{3,4}|[0-9a-f]{6}|[0-9a-f]{8})$/);
     if (hexm) {
         var h = hexm[1];
-        if (h.length === 3) {
-            h = h.split('').map(function(c){return c+c}).join('');
-        }
-        return '#' + h;
-    }
-    var rgbm = val.match(/^rgba?\(([^)]+)\)$/);
-    if (rgbm) {
-        var parts = rgbm[1].split(',').map(function(p){return p.trim();});
-        var r = parseInt(parts[0])||0;
-        var g = parseInt(parts[1])||0;
-        var b = parseInt(parts[2])||0;
-        var a = parts[3] !== undefined ? parseFloat(parts[3]) : 1;
-        if (a >= 1) {
-            return '#' + [r,g,b].map(function(n){ return ('0'+(n&255).toString(16)).slice(-2); }).join('');
-        } else {
-            return 'rgba(' + [r,g,b,a].join(',') + ')';
-        }
-    }
-    // hsl/hsla -> rgb -> hex/rgba
-    var hslm = val.match(/^hsla?\(([^)]+)\)$/);
-    if (hslm) {
-        var parts = hslm[1].split(',').map(function(p){return p.trim();});
-        var h = parseFloat(parts[0]) || 0;
-        var s = (parts[1]||'0').replace('%',''); s = parseFloat(s)/100 || 0;
-        var l = (parts[2]||'0').replace('%',''); l = parseFloat(l)/100 || 0;
-        var a = parts[3] !== undefined ? parseFloat(parts[3]) : 1;
-        function hue2rgb(p, q, t) {
-            if (t < 0) t += 1;
-            if (t > 1) t -= 1;
-            if (t < 1/6) return p + (q - p) * 6 * t;
-            if (t < 1/2) return q;
-            if (t < 2/3) return p + (q - p) * (2/3 - t) * 6;
-            return p;
-        }
-        var r,g,b;
-        if (s === 0) { r = g = b = l; }
-        else {
-            var q = l < 0.5 ? l * (1 + s) : l + s - l * s;
-            var p = 2 * l - q;
-            var hk = (h % 360) / 360;
-            r = hue2rgb(p,q,hk + 1/3);
-            g = hue2rgb(p,q,hk);
-            b = hue2rgb(p,q,hk - 1/3);
+        if (h.length <= 4) h = h.split('').map(function(c){ return c + c; }).join('');
+        var a = h.length === 8 ? parseInt(h.slice(6, 8), 16) / 255 : 1;
+        return [parseInt(h.slice(0, 2), 16), parseInt(h.slice(2, 4), 16), parseInt(h.slice(4, 6), 16), a];
+    }
+    var fm = val.match(/^(rgba?|hsla?)\(([^)]*)\)$/);
+    if (!fm) return null;
+    var args = fm[2].trim().split(/\s*,\s*|\s*\/\s*|\s+/);
+    if (args.length < 3 || args.length > 4) return null;
+    function num(s) { var n = parseFloat(s); return isNaN(n) ? null : n; }
+    function clamp(n, lo, hi) { return Math.min(hi, Math.max(lo, n)); }
+    function pct(s) { var n = num(s); return n === null ? null : (/%$/.test(s) ? n / 100 : n); }
+    var alpha = args.length === 4 ? pct(args[3]) : 1;
+    if (alpha === null) return null;
+    alpha = clamp(alpha, 0, 1);
+    if (fm[1].charAt(0) === 'r') {
+        var rgb = [];
+        for (var i = 0; i < 3; i++) {
+            var n = num(args[i]);
+            if (n === null) return null;
+            if (/%$/.test(args[i])) n = n * 2.55;
+            rgb.push(Math.round(clamp(n, 0, 255)));
         }
-        var R = Math.round(r * 255), G = Math.round(g * 255), B = Math.round(b * 255);
-        if (a >= 1) return '#' + [R,G,B].map(function(n){ return ('0'+(n&255).toString(16)).slice(-2); }).join('');
-        return 'rgba(' + [R,G,B,a].join(',') + ')';
+        return [rgb[0], rgb[1], rgb[2], alpha];
+    }
+    var hue = num(args[0]);
+    var sat = num(args[1]);
+    var light = num(args[2]);
+    if (hue === null || sat === null || light === null) return null;
+    if (/grad$/.test(args[0])) hue = hue * 0.9;
+    else if (/rad$/.test(args[0])) hue = hue * 180 / Math.PI;
+    else if (/turn$/.test(args[0])) hue = hue * 360;
+    var hk = (((hue % 360) + 360) % 360) / 360;
+    var s = clamp(sat / 100, 0, 1);
+    var l = clamp(light / 100, 0, 1);
+    function hue2rgb(p, q, t) {
+        if (t < 0) t += 1;
+        if (t > 1) t -= 1;
+        if (t < 1/6) return p + (q - p) * 6 * t;
+        if (t < 1/2) return q;
+        if (t < 2/3) return p + (q - p) * (2/3 - t) * 6;
+        return p;
     }
-    var named = {'red':'#ff0000','green':'#008000','blue':'#0000ff','black':'#000000','white':'#ffffff'};
-    if (named[val]) return named[val];
-    return val;
+    var r, g, b;
+    if (s === 0) { r = g = b = l; }
+    else {
+        var q = l < 0.5 ? l * (1 + s) : l + s - l * s;
+        var p = 2 * l - q;
+        r = hue2rgb(p, q, hk + 1/3);
+        g = hue2rgb(p, q, hk);
+        b = hue2rgb(p, q, hk - 1/3);
+    }
+    return [Math.round(r * 255), Math.round(g * 255), Math.round(b * 255), alpha];
+}
+
+// Normalize a color to its canonical form: "#rrggbb" when opaque, otherwise
+// "rgba(r,g,b,a)" with alpha rounded to 3 decimals. Unparseable values are
+// returned trimmed and lowercased.
+function normalizeColor(val) {
+    if (!val) return val;
+    var c = __rfox_parse_color(val);
+    if (!c) return String(val).trim().toLowerCase();
+    if (c[3] >= 1) {
+        return '#' + c.slice(0, 3).map(function(n){ return ('0' + n.toString(16)).slice(-2); }).join('');
+    }
+    return 'rgba(' + [c[0], c[1], c[2], Math.round(c[3] * 1000) / 1000].join(',') + ')';
 }
 
 function normalizeUnit(val) {
@@ -278,10 +608,11 @@ function normalizeUnit(val) {
     return s;
 }
 
-// getComputedStyle that applies rules by specificity & order, with inline style winning
-// Normalizes colors and common unit properties
-function getComputedStyle(el) {
-    if (!el || !el.getAttribute) return { getPropertyValue: function() { return ''; } };
+// Declarations applying to an element in cascade order (the last one for a
+// property wins): stylesheet rules by specificity & order, then inline style,
+// then `!important` declarations in the same order. Custom property names
+// (`--x`) keep their case.
+function __rfox_cascade_entries(el) {
     var matched = [];
     for (var i=0;i<__rfox_rules.length;i++) {
         var r = __rfox_rules[i];
@@ -293,19 +624,115 @@ function getComputedStyle(el) {
         if (a.specificity !== b.specificity) return a.specificity - b.specificity;
         return a.order - b.order;
     });
-    var decls = {};
+    var normal = [], important = [];
+    function add(k, v, selector, specificity, origin) {
+        k = k.trim();
+        var imp = /!\s*important\s*$/i.test(v);
+        var entry = {
+            name: k.indexOf('--') === 0 ? k : k.toLowerCase(),
+            value: imp ? v.replace(/!\s*important\s*$/i, '').trim() : v,
+            selector: selector, specificity: specificity, important: imp, origin: origin
+        };
+        (imp ? important : normal).push(entry);
+    }
     for (var j=0;j<matched.length;j++) {
         var d = matched[j].decls;
-        for (var k in d) { if (Object.prototype.hasOwnProperty.call(d,k)) decls[k.toLowerCase()] = d[k]; }
+        for (var k in d) { if (Object.prototype.hasOwnProperty.call(d,k)) add(k, d[k], matched[j].selector, matched[j].specificity, 'stylesheet'); }
     }
     // inline style overrides
     var styleAttr = el.getAttribute('style') || '';
-    styleAttr.split(';').forEach(function(s) { var p = s.split(':'); if (p.length === 2) decls[p[0].trim().toLowerCase()] = p[1].trim(); });
+    styleAttr.split(';').forEach(function(s) { var p = s.split(':'); if (p.length === 2) add(p[0], p[1].trim(), '', 1000000, 'inline'); });
+    return normal.concat(important);
+}
+
+function __rfox_cascade(el) {
+    var decls = {};
+    __rfox_cascade_entries(el).forEach(function(e) { decls[e.name] = e.value; });
+    return decls;
+}
+
+// Backs `RFEngine::explain_style`: the declarations of `prop` on the first
+// element matching `selector`, in cascade order, or null without a match.
+function __rfox_explain_style(selector, prop) {
+    var el = querySelector(selector);
+    if (!el || !el.tag) return null;
+    prop = String(prop).trim();
+    if (prop.indexOf('--') !== 0) prop = prop.toLowerCase();
+    return __rfox_cascade_entries(el).filter(function(e) { return e.name === prop; }).map(function(e) {
+        return { selector: e.selector, value: e.value, specificity: e.specificity, important: e.important, origin: e.origin };
+    });
+}
+
+// Substitute `var(--x, fallback)` references in a value. Returns null when a
+// reference has neither a value nor a fallback (the declaration is then invalid).
+function __rfox_resolve_vars(value, vars, depth) {
+    value = String(value);
+    if (value.indexOf('var(') === -1) return value;
+    if ((depth || 0) > 16) return null;
+    var out = '';
+    var pos = 0;
+    var start;
+    while ((start = value.indexOf('var(', pos)) !== -1) {
+        out += value.slice(pos, start);
+        var level = 1, comma = -1, i = start + 4;
+        for (; i < value.length && level > 0; i++) {
+            var c = value[i];
+            if (c === '(') level++;
+            else if (c === ')') level--;
+            else if (c === ',' && level === 1 && comma === -1) comma = i;
+        }
+        var end = i - 1;
+        var name = value.slice(start + 4, comma === -1 ? end : comma).trim();
+        var sub = Object.prototype.hasOwnProperty.call(vars, name) ? __rfox_resolve_vars(vars[name], vars, (depth || 0) + 1) : null;
+        if (sub === null && comma !== -1) sub = __rfox_resolve_vars(value.slice(comma + 1, end).trim(), vars, (depth || 0) + 1);
+        if (sub === null) return null;
+        out += sub;
+        pos = end + 1;
+    }
+    return out + value.slice(pos);
+}
+
+// Custom properties in effect on an element: inherited from its parent, then
+// overridden by its own `--x` declarations (resolved against the inherited set).
+function __rfox_custom_props(el, decls) {
+    var vars = {};
+    var pidx = el.parent;
+    if (pidx !== null && pidx !== undefined && __rfox_node(pidx)) {
+        vars = __rfox_custom_props(__rfox_node(pidx), __rfox_cascade(__rfox_node(pidx)));
+    }
+    var own = {};
+    for (var k in vars) { own[k] = vars[k]; }
+    for (var k in decls) {
+        if (k.indexOf('--') === 0) own[k] = decls[k];
+    }
+    var resolved = {};
+    for (var k in own) {
+        var v = __rfox_resolve_vars(own[k], own, 1);
+        if (v !== null) resolved[k] = v;
+    }
+    return resolved;
+}
+
+// getComputedStyle that applies rules by specificity & order, with inline style winning
+// Normalizes colors and common unit properties and resolves var() references
+function getComputedStyle(el) {
+    if (!el || !el.getAttribute) return { getPropertyValue: function() { return ''; } };
+    var decls = __rfox_cascade(el);
+    var vars = null;
 
     return {
         getPropertyValue: function(prop) {
-            var key = prop.toLowerCase();
-            var v = decls[key];
+            var key = String(prop).trim();
+            if (key.indexOf('--') !== 0) key = key.toLowerCase();
+            var v;
+            if (key.indexOf('--') === 0 || String(decls[key]).indexOf('var(') !== -1) {
+                if (vars === null) vars = __rfox_custom_props(el, decls);
+                v = key.indexOf('--') === 0 ? vars[key] : __rfox_resolve_vars(decls[key], vars);
+                if (v === null || v === undefined) return '';
+                if (key.indexOf('--') === 0) return String(v).trim();
+            } else {
+                v = decls[key];
+            }
             if (v === undefined) return '';
             if (key.indexOf('color') !== -1 || key === 'background') return normalizeColor(v);
             var unitProps = ['font-size','margin','margin-top','margin-bottom','padding','padding-top','padding-bottom','width','height'];
@@ -316,10 +743,93 @@ function getComputedStyle(el) {
 } 
 
 var __rfox_console = [];
-var document = { title: (typeof __RFOX_TITLE__ !== 'undefined' ? __RFOX_TITLE__ : "Title"), body: (typeof __RFOX_BODY__ !== 'undefined' ? __RFOX_BODY__ : "Body"), styles: __rfox_styles, querySelector: querySelector, querySelectorAll: querySelectorAll };
-// Provide a `window` alias for tests that reference it (falls back to globalThis)
-var window = (typeof window !== 'undefined') ? window : (typeof globalThis !== 'undefined' ? globalThis : this);
-var console = { log: function() { var txt = Array.prototype.slice.call(arguments).join(' '); var st=''; try{ st=(new Error()).stack || (new Error()).toString(); }catch(e){} if (typeof __rfox_console_log === 'function') { try{ __rfox_console_log(txt, st); }catch(e){} } else { __rfox_console.push(txt); } }, error: function() { var txt = Array.prototype.slice.call(arguments).join(' '); var st=''; try{ st=(new Error()).stack || (new Error()).toString(); }catch(e){} if (typeof __rfox_console_error === 'function') { try{ __rfox_console_error(txt, st); }catch(e){} } else { __rfox_console.push(txt); } } };
+var document = { title: (typeof __RFOX_TITLE__ !== 'undefined' ? __RFOX_TITLE__ : "Title"), body: (typeof __RFOX_BODY__ !== 'undefined' ? __RFOX_BODY__ : "Body"), styles: __rfox_styles, querySelector: querySelector, querySelectorAll: querySelectorAll,
+    createElement: function(tag) { return __wrap_el({ tag: String(tag).toLowerCase(), id: "", class: "", text: "", attributes: [], parent: null }); } };
+// `window` is the global object; when the allowlist leaves it out, event
+// listeners for it live on a private stand-in instead
+var window = __rfox_global_allowed('window') ? globalThis : {};
+// console.group nesting; messages are indented two spaces per level
+var __rfox_group_depth = 0;
+function __rfox_indent(txt) {
+    if (!__rfox_group_depth) return txt;
+    var pad = '  '.repeat(__rfox_group_depth);
+    return String(txt).split('\n').map(function(l) { return pad + l; }).join('\n');
+}
+// Stack reported with a console message: that of a logged Error when it has
+// one (as browsers show it), else the current one. Boa leaves
+// `Error.prototype.stack` unset, so the latter is usually just "Error".
+function __rfox_call_stack(args) {
+    for (var i = 0; i < args.length; i++) {
+        if (args[i] instanceof Error && typeof args[i].stack === 'string' && args[i].stack) return args[i].stack;
+    }
+    try { return (new Error()).stack || (new Error()).toString(); } catch(e) { return ''; }
+}
+var console = { log: function() { var txt = __rfox_indent(Array.prototype.slice.call(arguments).join(' ')); var st=__rfox_call_stack(arguments); if (typeof __rfox_console_log === 'function') { try{ __rfox_console_log(txt, st, __rfox_group_depth); }catch(e){} } else { __rfox_console.push(txt); } }, error: function() { var txt = __rfox_indent(Array.prototype.slice.call(arguments).join(' ')); var st=__rfox_call_stack(arguments); if (typeof __rfox_console_error === 'function') { try{ __rfox_console_error(txt, st, __rfox_group_depth); }catch(e){} } else { __rfox_console.push(txt); } } };
+
+// console.table / console.dir: format to text and forward through console.log
+function __rfox_fmt_cell(v) {
+    if (v === undefined) return '';
+    if (typeof v === 'string') return v;
+    try { var j = JSON.stringify(v); return (j === undefined) ? String(v) : j; } catch(e) { return String(v); }
+}
+console.table = function(data, columns) {
+    if (data === null || typeof data !== 'object') { console.log(data); return; }
+    var keys = Object.keys(data);
+    var cols = [];
+    var hasValues = false;
+    keys.forEach(function(k) {
+        var row = data[k];
+        if (row !== null && typeof row === 'object') {
+            Object.keys(row).forEach(function(c) { if (cols.indexOf(c) < 0) cols.push(c); });
+        } else {
+            hasValues = true;
+        }
+    });
+    if (Array.isArray(columns)) cols = cols.filter(function(c) { return columns.indexOf(c) >= 0; });
+    var header = ['(index)'].concat(cols);
+    if (hasValues) header.push('Values');
+    var rows = keys.map(function(k) {
+        var row = data[k];
+        var isObj = row !== null && typeof row === 'object';
+        var cells = [k].concat(cols.map(function(c) { return isObj ? __rfox_fmt_cell(row[c]) : ''; }));
+        if (hasValues) cells.push(isObj ? '' : __rfox_fmt_cell(row));
+        return cells;
+    });
+    var widths = header.map(function(h, i) {
+        return rows.reduce(function(w, r) { return Math.max(w, r[i].length); }, h.length);
+    });
+    var line = function(cells) { return cells.map(function(c, i) { return c + ' '.repeat(widths[i] - c.length); }).join(' | ').replace(/\s+$/, ''); };
+    var out = [line(header), widths.map(function(w) { return '-'.repeat(w); }).join('-+-')];
+    rows.forEach(function(r) { out.push(line(r)); });
+    console.log(out.join('\n'));
+};
+console.dir = function(obj) {
+    var txt;
+    try { txt = JSON.stringify(obj, null, 2); } catch(e) { txt = undefined; }
+    console.log(txt === undefined ? String(obj) : txt);
+};
+console.group = function() {
+    if (arguments.length) console.log.apply(console, arguments);
+    __rfox_group_depth++;
+};
+console.groupCollapsed = console.group;
+console.groupEnd = function() { if (__rfox_group_depth > 0) __rfox_group_depth--; };
+console.assert = function(cond) {
+    if (cond) return;
+    var rest = Array.prototype.slice.call(arguments, 1);
+    console.error(rest.length ? 'Assertion failed: ' + rest.join(' ') : 'Assertion failed');
+};
+var __rfox_counts = {};
+console.count = function(label) {
+    label = (label === undefined) ? 'default' : String(label);
+    __rfox_counts[label] = (__rfox_counts[label] || 0) + 1;
+    console.log(label + ': ' + __rfox_counts[label]);
+};
+// The host only distinguishes log and error levels; warnings go to error
+console.warn = console.error;
+console.countReset = function(label) {
+    __rfox_counts[(label === undefined) ? 'default' : String(label)] = 0;
+};
 
 // Microtask & macrotask (timer) support for M1
 var __rfox_microtasks = [];
@@ -328,33 +838,46 @@ function queueMicrotask(fn) {
         __rfox_microtasks.push(fn);
     }
 }
+// Drains both the harness queue and the engine's native promise jobs (via
+// `__rfox_run_jobs` when the host registers it), so chained `.then`
+// continuations settle before returning.
 function __rfox_run_microtasks() {
-    while (__rfox_microtasks.length) {
-        var f = __rfox_microtasks.shift();
-        try { f(); } catch(e) { try { console.error('microtask error', e); } catch(_) {} }
+    while (true) {
+        while (__rfox_microtasks.length) {
+            var f = __rfox_microtasks.shift();
+            try { f(); } catch(e) { try { console.error('microtask error', e); } catch(_) {} }
+        }
+        if (typeof __rfox_run_jobs !== 'function') break;
+        __rfox_run_jobs();
+        if (!__rfox_microtasks.length) break;
     }
 }
 
 var __rfox_now = 0; // logical time in ms for deterministic timers
 var __rfox_macrotasks = []; // {id, fn, due, interval}
 var __rfox_next_timer_id = 1;
-function __rfox_enqueue_macrotask(fn, due, id, interval) {
-    __rfox_macrotasks.push({ id: id, fn: fn, due: due === undefined ? __rfox_now : due, interval: interval || 0 });
+var __rfox_running_timer = null; // macrotask currently executing, if any
+// Upper bound on tasks one `__rfox_run_until_idle` call runs (`max_event_loop_steps`)
+var __rfox_max_event_loop_steps = (typeof __RFOX_MAX_EVENT_LOOP_STEPS__ !== 'undefined' ? __RFOX_MAX_EVENT_LOOP_STEPS__ : 10000);
+function __rfox_enqueue_macrotask(fn, due, id, interval, repeat) {
+    __rfox_macrotasks.push({ id: id, fn: fn, due: due === undefined ? __rfox_now : due, interval: interval || 0, repeat: !!repeat });
 }
 
 function setTimeout(fn, delay) {
-    var cb = (typeof fn === 'function') ? fn : function() { try { eval(fn); } catch(e) {} };
+    var cb = (typeof fn === 'function') ? fn : function() { try { (0, eval)(fn); } catch(e) {} };
     var id = __rfox_next_timer_id++;
     __rfox_enqueue_macrotask(cb, __rfox_now + (delay||0), id, 0);
     return id;
 }
 function clearTimeout(id) {
+    // Clearing from inside its own callback stops an interval from rescheduling
+    if (__rfox_running_timer && __rfox_running_timer.id === id) { __rfox_running_timer.cleared = true; }
     for (var i=0;i<__rfox_macrotasks.length;i++) { if (__rfox_macrotasks[i].id === id) { __rfox_macrotasks.splice(i,1); return; } }
 }
 function setInterval(fn, interval) {
-    var cb = (typeof fn === 'function') ? fn : function() { try { eval(fn); } catch(e) {} };
+    var cb = (typeof fn === 'function') ? fn : function() { try { (0, eval)(fn); } catch(e) {} };
     var id = __rfox_next_timer_id++;
-    __rfox_enqueue_macrotask(cb, __rfox_now + (interval||0), id, interval||0);
+    __rfox_enqueue_macrotask(cb, __rfox_now + (interval||0), id, interval||0, true);
     return id;
 }
 function clearInterval(id) { clearTimeout(id); }
@@ -367,10 +890,38 @@ function __rfox_run_one_macrotask() {
     }
     if (idx === -1) return false;
     var t = __rfox_macrotasks.splice(idx,1)[0];
+    __rfox_running_timer = t;
     try { t.fn(); } catch(e) { try { console.error('macrotask error', e); } catch(_) {} }
+    __rfox_running_timer = null;
     // if interval, reschedule
-    if (t.interval && t.interval > 0) {
-        __rfox_enqueue_macrotask(t.fn, __rfox_now + t.interval, t.id, t.interval);
+    if (t.repeat && !t.cleared) {
+        __rfox_enqueue_macrotask(t.fn, __rfox_now + t.interval, t.id, t.interval, true);
+    }
+    return true;
+}
+
+// requestAnimationFrame on the logical clock: pending callbacks run together
+// as one frame at the next 16ms boundary. Callbacks requested during a frame
+// wait for the next one, so animation loops can't spin forever.
+var __rfox_frame_interval = 16;
+var __rfox_frame_callbacks = []; // {id, fn}
+function requestAnimationFrame(fn) {
+    if (typeof fn !== 'function') { throw new TypeError('requestAnimationFrame callback must be a function'); }
+    var id = __rfox_next_timer_id++;
+    __rfox_frame_callbacks.push({ id: id, fn: fn });
+    return id;
+}
+function cancelAnimationFrame(id) {
+    for (var i=0;i<__rfox_frame_callbacks.length;i++) { if (__rfox_frame_callbacks[i].id === id) { __rfox_frame_callbacks.splice(i,1); return; } }
+}
+function __rfox_run_animation_frame() {
+    if (!__rfox_frame_callbacks.length) return false;
+    var batch = __rfox_frame_callbacks;
+    __rfox_frame_callbacks = [];
+    __rfox_now = (Math.floor(__rfox_now / __rfox_frame_interval) + 1) * __rfox_frame_interval;
+    for (var i=0;i<batch.length;i++) {
+        try { batch[i].fn(__rfox_now); } catch(e) { try { console.error('animation frame error', e); } catch(_) {} }
+        __rfox_run_microtasks();
     }
     return true;
 }
@@ -378,15 +929,24 @@ function __rfox_run_one_macrotask() {
 function __rfox_run_until_idle(max_iters) {
     var iters = 0;
     var executed = false;
-    max_iters = max_iters || 10000; // safety bound
+    var frame_ran = false;
+    var capped = true;
+    max_iters = max_iters || __rfox_max_event_loop_steps; // safety bound
     while (iters++ < max_iters) {
         __rfox_run_microtasks();
         var ran = __rfox_run_one_macrotask();
-        if (!ran) break;
+        if (!ran) {
+            // At most one animation frame per call, once due timers are done
+            if (frame_ran || !__rfox_run_animation_frame()) { capped = false; break; }
+            frame_ran = true;
+        }
         executed = true;
     }
     // final microtasks drain
     __rfox_run_microtasks();
+    if (capped && (__rfox_frame_callbacks.length || __rfox_macrotasks.some(function(t) { return t.due <= __rfox_now; }))) {
+        console.warn('__rfox_run_until_idle: stopped after ' + max_iters + ' steps with tasks still pending (max_event_loop_steps)');
+    }
     return executed;
 }
 
@@ -437,7 +997,825 @@ function __rfox_tick(ms) {
         SimplePromise.reject = function(e){ return new SimplePromise(function(_,rej){ rej(e); }); };
         Promise = SimplePromise;
     }
+    // Combinators missing from older engines (and from the polyfill above)
+    if (typeof Promise.allSettled !== 'function') {
+        Promise.allSettled = function(items) {
+            return Promise.all(Array.prototype.map.call(items, function(p) {
+                return Promise.resolve(p).then(
+                    function(value) { return { status: 'fulfilled', value: value }; },
+                    function(reason) { return { status: 'rejected', reason: reason }; });
+            }));
+        };
+    }
+    if (typeof Promise.any !== 'function') {
+        Promise.any = function(items) {
+            var list = Array.prototype.slice.call(items);
+            return new Promise(function(resolve, reject) {
+                var errors = new Array(list.length), pending = list.length;
+                var fail = function() {
+                    var err = typeof AggregateError === 'function' ? new AggregateError(errors, 'All promises were rejected') : new Error('All promises were rejected');
+                    err.errors = errors;
+                    reject(err);
+                };
+                if (!pending) { fail(); return; }
+                list.forEach(function(p, i) {
+                    Promise.resolve(p).then(resolve, function(reason) {
+                        errors[i] = reason;
+                        if (--pending === 0) { fail(); }
+                    });
+                });
+            });
+        };
+    }
 })();
 
-// Expose helper for manual flushing from host-side tests
-// __rfox_run_until_idle() is already available to call from host-injected script
+// Base64 (btoa/atob) over Latin-1 strings. Uses the host's native base64
+// codec when registered (`__rfox_btoa`/`__rfox_atob`), else a JS fallback.
+var __rfox_b64 = 'ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/';
+function btoa(s) {
+    s = String(s);
+    for (var i = 0; i < s.length; i++) {
+        if (s.charCodeAt(i) > 255) { throw new Error('InvalidCharacterError: btoa input contains characters outside Latin-1'); }
+    }
+    if (typeof __rfox_btoa === 'function') { return __rfox_btoa(s); }
+    var out = '';
+    for (var j = 0; j < s.length; j += 3) {
+        var a = s.charCodeAt(j), b = s.charCodeAt(j + 1), c = s.charCodeAt(j + 2);
+        var n = (a << 16) | ((b || 0) << 8) | (c || 0);
+        out += __rfox_b64.charAt(n >> 18) + __rfox_b64.charAt((n >> 12) & 63) +
+            (j + 1 < s.length ? __rfox_b64.charAt((n >> 6) & 63) : '=') +
+            (j + 2 < s.length ? __rfox_b64.charAt(n & 63) : '=');
+    }
+    return out;
+}
+function atob(s) {
+    s = String(s).replace(/[\t\n\f\r ]/g, '');
+    if (typeof __rfox_atob === 'function') { return __rfox_atob(s); }
+    s = s.replace(/=+$/, '');
+    if (s.length % 4 === 1 || /[^A-Za-z0-9+\/]/.test(s)) { throw new Error('InvalidCharacterError: atob input is not valid base64'); }
+    var out = '', bits = 0, acc = 0;
+    for (var i = 0; i < s.length; i++) {
+        acc = (acc << 6) | __rfox_b64.indexOf(s.charAt(i));
+        bits += 6;
+        if (bits >= 8) { bits -= 8; out += String.fromCharCode((acc >> bits) & 255); }
+    }
+    return out;
+}
+
+// UTF-8 TextEncoder/TextDecoder
+function TextEncoder() { this.encoding = 'utf-8'; }
+TextEncoder.prototype.encode = function(s) {
+    s = s === undefined ? '' : String(s);
+    var bytes = [];
+    for (var i = 0; i < s.length; i++) {
+        var cp = s.codePointAt(i);
+        if (cp > 0xffff) { i++; }
+        if (cp >= 0xd800 && cp <= 0xdfff) { cp = 0xfffd; } // lone surrogate
+        if (cp < 0x80) { bytes.push(cp); }
+        else if (cp < 0x800) { bytes.push(0xc0 | (cp >> 6), 0x80 | (cp & 63)); }
+        else if (cp < 0x10000) { bytes.push(0xe0 | (cp >> 12), 0x80 | ((cp >> 6) & 63), 0x80 | (cp & 63)); }
+        else { bytes.push(0xf0 | (cp >> 18), 0x80 | ((cp >> 12) & 63), 0x80 | ((cp >> 6) & 63), 0x80 | (cp & 63)); }
+    }
+    return new Uint8Array(bytes);
+};
+function TextDecoder(label) {
+    this.encoding = 'utf-8';
+    if (label !== undefined && !/^utf-?8$/i.test(String(label))) { throw new RangeError('TextDecoder only supports utf-8'); }
+}
+TextDecoder.prototype.decode = function(input) {
+    var b = input === undefined ? [] : input instanceof ArrayBuffer ? new Uint8Array(input) : input;
+    var out = '';
+    for (var i = 0; i < b.length;) {
+        var c = b[i], need = c < 0x80 ? 0 : c >= 0xf0 && c < 0xf8 ? 3 : c >= 0xe0 ? 2 : c >= 0xc0 ? 1 : -1;
+        var cp = need === 0 ? c : need === 1 ? c & 31 : need === 2 ? c & 15 : c & 7;
+        var ok = need >= 0;
+        for (var k = 1; ok && k <= need; k++) {
+            if (i + k >= b.length || (b[i + k] & 0xc0) !== 0x80) { ok = false; } else { cp = (cp << 6) | (b[i + k] & 63); }
+        }
+        if (!ok || cp > 0x10ffff) { out += '\ufffd'; i += 1; continue; }
+        out += String.fromCodePoint(cp);
+        i += need + 1;
+    }
+    return out;
+};
+
+// WHATWG-style URL and URLSearchParams (pure JS). Covers absolute and relative
+// parsing, dot-segment removal, default-port elision and percent-encoding of
+// unsafe characters; IDNA/punycode hosts are not converted.
+var __rfox_special_ports = { 'http:': '80', 'https:': '443', 'ws:': '80', 'wss:': '443', 'ftp:': '21', 'file:': '' };
+function __rfox_pct(s, extra) {
+    var out = '';
+    for (var i = 0; i < s.length; i++) {
+        var c = s.charAt(i), code = s.charCodeAt(i);
+        if (code >= 0xd800 && code <= 0xdbff && i + 1 < s.length) { c += s.charAt(++i); }
+        if (code <= 0x20 || code >= 0x7f || '"<>`'.indexOf(c) >= 0 || (extra && extra.indexOf(c) >= 0)) {
+            out += c === "'" ? '%27' : encodeURIComponent(c);
+        }
+        else { out += c; }
+    }
+    return out;
+}
+function __rfox_remove_dots(path) {
+    var out = [];
+    var segs = path.split('/');
+    for (var i = 0; i < segs.length; i++) {
+        var s = segs[i];
+        if (s === '..') { if (out.length > 1) { out.pop(); } if (i === segs.length - 1) { out.push(''); } }
+        else if (s === '.') { if (i === segs.length - 1) { out.push(''); } }
+        else { out.push(s); }
+    }
+    var joined = out.join('/');
+    return joined.charAt(0) === '/' ? joined : '/' + joined;
+}
+function __rfox_url_parse(input, base) {
+    var s = String(input).replace(/^[\x00-\x20]+|[\x00-\x20]+$/g, '').replace(/[\t\n\r]/g, '');
+    var m = /^([a-zA-Z][a-zA-Z0-9+.\-]*):(.*)$/.exec(s);
+    var u = { protocol: '', username: '', password: '', hostname: '', port: '', pathname: '', query: null, fragment: null, opaque: false };
+    var rest;
+    if (m) {
+        u.protocol = m[1].toLowerCase() + ':';
+        rest = m[2];
+    } else {
+        if (!base) { throw new TypeError('Invalid URL: ' + s); }
+        var b = base;
+        if (b.opaque) { throw new TypeError('Invalid URL: ' + s); }
+        u.protocol = b.protocol;
+        if (s.indexOf('//') === 0) { rest = s; }
+        else {
+            u.username = b.username; u.password = b.password; u.hostname = b.hostname; u.port = b.port;
+            var hashAt = s.indexOf('#');
+            var frag = hashAt >= 0 ? s.slice(hashAt + 1) : null;
+            var noFrag = hashAt >= 0 ? s.slice(0, hashAt) : s;
+            var qAt = noFrag.indexOf('?');
+            var query = qAt >= 0 ? noFrag.slice(qAt + 1) : null;
+            var path = qAt >= 0 ? noFrag.slice(0, qAt) : noFrag;
+            if (path === '') {
+                u.pathname = b.pathname;
+                u.query = query !== null ? query : (hashAt >= 0 && qAt < 0 ? b.query : (noFrag === '' ? b.query : null));
+            } else if (path.charAt(0) === '/') {
+                u.pathname = __rfox_remove_dots(path);
+                u.query = query;
+            } else {
+                var dir = b.pathname.slice(0, b.pathname.lastIndexOf('/') + 1);
+                u.pathname = __rfox_remove_dots(dir + path);
+                u.query = query;
+            }
+            u.fragment = frag;
+            return __rfox_url_finish(u);
+        }
+    }
+    var special = __rfox_special_ports.hasOwnProperty(u.protocol);
+    if (special) { rest = rest.replace(/\\/g, '/'); }
+    var hashAt2 = rest.indexOf('#');
+    if (hashAt2 >= 0) { u.fragment = rest.slice(hashAt2 + 1); rest = rest.slice(0, hashAt2); }
+    var qAt2 = rest.indexOf('?');
+    if (qAt2 >= 0) { u.query = rest.slice(qAt2 + 1); rest = rest.slice(0, qAt2); }
+    if (rest.indexOf('//') === 0 || (special && !m)) {
+        rest = u.protocol === 'file:' ? rest.replace(/^\/\//, '') : rest.replace(/^\/+/, '');
+        var slash = rest.indexOf('/');
+        var auth = slash >= 0 ? rest.slice(0, slash) : rest;
+        rest = slash >= 0 ? rest.slice(slash) : '';
+        var at = auth.lastIndexOf('@');
+        if (at >= 0) {
+            var cred = auth.slice(0, at), colon = cred.indexOf(':');
+            u.username = __rfox_pct(colon >= 0 ? cred.slice(0, colon) : cred, ':@/');
+            u.password = colon >= 0 ? __rfox_pct(cred.slice(colon + 1), ':@/') : '';
+            auth = auth.slice(at + 1);
+        }
+        var pm = /^(\[[^\]]*\]|[^:]*)(?::(\d*))?$/.exec(auth);
+        if (!pm) { throw new TypeError('Invalid URL: ' + s); }
+        u.hostname = pm[1].toLowerCase();
+        u.port = pm[2] || '';
+        if (/[\s#%\/:<>?@\[\\\]^|]/.test(u.hostname.replace(/^\[|\]$/g, '')) && u.hostname.charAt(0) !== '[') { throw new TypeError('Invalid URL: ' + s); }
+        if (special && u.protocol !== 'file:' && u.hostname === '') { throw new TypeError('Invalid URL: ' + s); }
+        if (u.port !== '') {
+            if (Number(u.port) > 65535) { throw new TypeError('Invalid URL: ' + s); }
+            u.port = String(Number(u.port));
+        }
+        u.pathname = rest === '' ? (special ? '/' : '') : __rfox_remove_dots(rest);
+    } else if (special) {
+        throw new TypeError('Invalid URL: ' + s);
+    } else if (rest.charAt(0) === '/') {
+        u.pathname = __rfox_remove_dots(rest);
+    } else {
+        u.opaque = true;
+        u.pathname = rest;
+    }
+    return __rfox_url_finish(u);
+}
+function __rfox_url_finish(u) {
+    if (u.port === __rfox_special_ports[u.protocol]) { u.port = ''; }
+    u.pathname = u.opaque ? u.pathname : __rfox_pct(u.pathname, '?#');
+    if (u.query !== null) { u.query = __rfox_pct(u.query, '#' + (__rfox_special_ports.hasOwnProperty(u.protocol) ? "'" : '')); }
+    if (u.fragment !== null) { u.fragment = __rfox_pct(u.fragment, ''); }
+    return u;
+}
+function __rfox_url_href(u) {
+    var auth = '';
+    if (u.hostname !== '' || u.protocol === 'file:') {
+        var cred = u.username + (u.password ? ':' + u.password : '');
+        auth = '//' + (cred ? cred + '@' : '') + u.hostname + (u.port ? ':' + u.port : '');
+    }
+    return u.protocol + auth + u.pathname + (u.query !== null ? '?' + u.query : '') + (u.fragment !== null ? '#' + u.fragment : '');
+}
+function URL(url, base) {
+    if (!(this instanceof URL)) { throw new TypeError("Constructor URL requires 'new'"); }
+    var b = base === undefined ? null : (base instanceof URL ? base._u : __rfox_url_parse(String(base), null));
+    this._u = __rfox_url_parse(url instanceof URL ? url.href : String(url), b);
+    this._params = null;
+}
+URL.canParse = function(url, base) { try { new URL(url, base); return true; } catch (e) { return false; } };
+URL.prototype.toString = function() { return this.href; };
+URL.prototype.toJSON = function() { return this.href; };
+(function() {
+    function prop(name, get, set) { Object.defineProperty(URL.prototype, name, { get: get, set: set, enumerable: true, configurable: true }); }
+    prop('href', function() { return __rfox_url_href(this._u); }, function(v) {
+        this._u = __rfox_url_parse(String(v), null);
+        if (this._params) { this._params._list = __rfox_parse_query(this._u.query || ''); }
+    });
+    prop('origin', function() {
+        var u = this._u;
+        return __rfox_special_ports.hasOwnProperty(u.protocol) && u.protocol !== 'file:' ? u.protocol + '//' + u.hostname + (u.port ? ':' + u.port : '') : 'null';
+    });
+    prop('protocol', function() { return this._u.protocol; }, function(v) {
+        var p = String(v).replace(/:.*$/, '').toLowerCase();
+        if (/^[a-z][a-z0-9+.\-]*$/.test(p) && (__rfox_special_ports.hasOwnProperty(p + ':') === __rfox_special_ports.hasOwnProperty(this._u.protocol))) {
+            this._u.protocol = p + ':';
+            if (this._u.port === __rfox_special_ports[this._u.protocol]) { this._u.port = ''; }
+        }
+    });
+    prop('username', function() { return this._u.username; }, function(v) { if (this._u.hostname) { this._u.username = __rfox_pct(String(v), ':@/'); } });
+    prop('password', function() { return this._u.password; }, function(v) { if (this._u.hostname) { this._u.password = __rfox_pct(String(v), ':@/'); } });
+    prop('host', function() { return this._u.hostname + (this._u.port ? ':' + this._u.port : ''); }, function(v) {
+        if (this._u.opaque) { return; }
+        var m = /^([^:\/?#]*)(?::(\d*))?/.exec(String(v));
+        if (m && m[1]) { this._u.hostname = m[1].toLowerCase(); if (m[2] !== undefined) { this.port = m[2]; } }
+    });
+    prop('hostname', function() { return this._u.hostname; }, function(v) {
+        if (this._u.opaque) { return; }
+        var h = /^[^:\/?#]*/.exec(String(v))[0];
+        if (h) { this._u.hostname = h.toLowerCase(); }
+    });
+    prop('port', function() { return this._u.port; }, function(v) {
+        if (!this._u.hostname || this._u.protocol === 'file:') { return; }
+        var p = /^\d*/.exec(String(v))[0];
+        if (p === '' && String(v) !== '') { return; }
+        if (p !== '' && Number(p) > 65535) { return; }
+        this._u.port = p === '' || p === __rfox_special_ports[this._u.protocol] ? '' : String(Number(p));
+    });
+    prop('pathname', function() { return this._u.pathname; }, function(v) {
+        if (this._u.opaque) { return; }
+        var p = String(v);
+        if (__rfox_special_ports.hasOwnProperty(this._u.protocol)) { p = p.replace(/\\/g, '/'); }
+        if (p.charAt(0) !== '/') { p = '/' + p; }
+        this._u.pathname = __rfox_pct(__rfox_remove_dots(p), '?#');
+    });
+    prop('search', function() { return this._u.query ? '?' + this._u.query : ''; }, function(v) {
+        var q = String(v);
+        if (q.charAt(0) === '?') { q = q.slice(1); }
+        this._u.query = q === '' ? null : __rfox_url_finish({ protocol: this._u.protocol, port: '', pathname: '', opaque: true, query: q, fragment: null }).query;
+        if (this._params) { this._params._list = __rfox_parse_query(q); }
+    });
+    prop('hash', function() { return this._u.fragment ? '#' + this._u.fragment : ''; }, function(v) {
+        var h = String(v);
+        if (h.charAt(0) === '#') { h = h.slice(1); }
+        this._u.fragment = h === '' ? null : __rfox_pct(h, '');
+    });
+    prop('searchParams', function() {
+        if (!this._params) {
+            this._params = new URLSearchParams(this._u.query || '');
+            this._params._url = this;
+        }
+        return this._params;
+    });
+})();
+
+function __rfox_form_decode(s) {
+    s = s.replace(/\+/g, ' ');
+    try { return decodeURIComponent(s); } catch (e) { return s; }
+}
+function __rfox_form_encode(s) {
+    return encodeURIComponent(s).replace(/%20/g, '+').replace(/[!'()~]/g, function(c) { return '%' + c.charCodeAt(0).toString(16).toUpperCase(); });
+}
+function __rfox_parse_query(q) {
+    var list = [];
+    var parts = q.split('&');
+    for (var i = 0; i < parts.length; i++) {
+        if (parts[i] === '') { continue; }
+        var eq = parts[i].indexOf('=');
+        list.push(eq >= 0 ? [__rfox_form_decode(parts[i].slice(0, eq)), __rfox_form_decode(parts[i].slice(eq + 1))] : [__rfox_form_decode(parts[i]), '']);
+    }
+    return list;
+}
+function URLSearchParams(init) {
+    if (!(this instanceof URLSearchParams)) { throw new TypeError("Constructor URLSearchParams requires 'new'"); }
+    this._url = null;
+    this._list = [];
+    if (init === undefined || init === null) { return; }
+    if (init instanceof URLSearchParams) { this._list = init._list.map(function(p) { return [p[0], p[1]]; }); }
+    else if (typeof init === 'object' && typeof init.length === 'number') {
+        for (var i = 0; i < init.length; i++) {
+            if (!init[i] || init[i].length !== 2) { throw new TypeError('URLSearchParams pairs must have exactly two items'); }
+            this._list.push([String(init[i][0]), String(init[i][1])]);
+        }
+    } else if (typeof init === 'object') {
+        for (var k in init) { if (Object.prototype.hasOwnProperty.call(init, k)) { this._list.push([k, String(init[k])]); } }
+    } else {
+        var q = String(init);
+        this._list = __rfox_parse_query(q.charAt(0) === '?' ? q.slice(1) : q);
+    }
+}
+URLSearchParams.prototype._update = function() {
+    if (this._url) { var q = this.toString(); this._url._u.query = q === '' ? null : q; }
+};
+URLSearchParams.prototype.append = function(name, value) { this._list.push([String(name), String(value)]); this._update(); };
+URLSearchParams.prototype['delete'] = function(name, value) {
+    name = String(name);
+    this._list = this._list.filter(function(p) { return p[0] !== name || (value !== undefined && p[1] !== String(value)); });
+    this._update();
+};
+URLSearchParams.prototype.get = function(name) {
+    name = String(name);
+    for (var i = 0; i < this._list.length; i++) { if (this._list[i][0] === name) { return this._list[i][1]; } }
+    return null;
+};
+URLSearchParams.prototype.getAll = function(name) {
+    name = String(name);
+    return this._list.filter(function(p) { return p[0] === name; }).map(function(p) { return p[1]; });
+};
+URLSearchParams.prototype.has = function(name, value) {
+    name = String(name);
+    return this._list.some(function(p) { return p[0] === name && (value === undefined || p[1] === String(value)); });
+};
+URLSearchParams.prototype.set = function(name, value) {
+    name = String(name); value = String(value);
+    var found = false;
+    this._list = this._list.filter(function(p) {
+        if (p[0] !== name) { return true; }
+        if (found) { return false; }
+        found = true; p[1] = value; return true;
+    });
+    if (!found) { this._list.push([name, value]); }
+    this._update();
+};
+URLSearchParams.prototype.sort = function() {
+    // Stable sort by name (Array.prototype.sort is stable in ES2019+)
+    this._list.sort(function(a, b) { return a[0] < b[0] ? -1 : a[0] > b[0] ? 1 : 0; });
+    this._update();
+};
+URLSearchParams.prototype.forEach = function(fn, thisArg) {
+    var list = this._list.slice();
+    for (var i = 0; i < list.length; i++) { fn.call(thisArg, list[i][1], list[i][0], this); }
+};
+URLSearchParams.prototype.entries = function() { return this._list.map(function(p) { return [p[0], p[1]]; })[Symbol.iterator](); };
+URLSearchParams.prototype.keys = function() { return this._list.map(function(p) { return p[0]; })[Symbol.iterator](); };
+URLSearchParams.prototype.values = function() { return this._list.map(function(p) { return p[1]; })[Symbol.iterator](); };
+URLSearchParams.prototype[Symbol.iterator] = URLSearchParams.prototype.entries;
+URLSearchParams.prototype.toString = function() {
+    return this._list.map(function(p) { return __rfox_form_encode(p[0]) + '=' + __rfox_form_encode(p[1]); }).join('&');
+};
+Object.defineProperty(URLSearchParams.prototype, 'size', { get: function() { return this._list.length; }, configurable: true });
+
+// `location` for the loaded page (read-only snapshot; assignments do not navigate)
+var location = new URL(typeof __RFOX_URL__ !== 'undefined' ? __RFOX_URL__ : 'about:blank');
+document.URL = location.href;
+document.location = location;
+// `<base href>` when the page has one, for resolving relative URLs
+document.baseURI = (typeof __RFOX_BASE_URL__ !== 'undefined' && __RFOX_BASE_URL__) ? __RFOX_BASE_URL__ : location.href;
+
+// Web Crypto subset: `crypto.getRandomValues` and `crypto.subtle.digest`.
+// With `EngineConfig::random_seed` set, random values (and `Math.random`)
+// come from a seeded mulberry32 generator so runs are reproducible.
+var __rfox_random_seed = (typeof __RFOX_RANDOM_SEED__ !== 'undefined' ? __RFOX_RANDOM_SEED__ : null);
+var __rfox_random_u32 = (function() {
+    if (__rfox_random_seed === null) {
+        return function() { return Math.floor(Math.random() * 4294967296) >>> 0; };
+    }
+    var state = (__rfox_random_seed[1] ^ Math.imul(__rfox_random_seed[0], 0x9e3779b9)) >>> 0;
+    return function() {
+        state = (state + 0x6d2b79f5) >>> 0;
+        var t = state;
+        t = Math.imul(t ^ (t >>> 15), t | 1);
+        t ^= t + Math.imul(t ^ (t >>> 7), t | 61);
+        return (t ^ (t >>> 14)) >>> 0;
+    };
+})();
+if (__rfox_random_seed !== null) {
+    Math.random = function() { return __rfox_random_u32() / 4294967296; };
+}
+
+// Bytes of a BufferSource (ArrayBuffer, typed array or DataView)
+function __rfox_buffer_bytes(data) {
+    if (data instanceof ArrayBuffer) { return new Uint8Array(data); }
+    if (data && data.buffer instanceof ArrayBuffer) { return new Uint8Array(data.buffer, data.byteOffset, data.byteLength); }
+    throw new TypeError('Expected an ArrayBuffer, typed array or DataView');
+}
+
+// Pure-JS SHA-256, used when the native digest bridge is not registered
+function __rfox_sha256_hex(bytes) {
+    var K = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2
+    ];
+    var H = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+    var len = bytes.length;
+    var padded = new Uint8Array(((len + 9 + 63) >> 6) << 6);
+    padded.set(bytes);
+    padded[len] = 0x80;
+    var bits = len * 8;
+    for (var b = 0; b < 8; b++) { padded[padded.length - 1 - b] = b < 4 ? (bits >>> (8 * b)) & 0xff : Math.floor(bits / 4294967296 / Math.pow(256, b - 4)) & 0xff; }
+    var w = new Array(64);
+    function rotr(x, n) { return (x >>> n) | (x << (32 - n)); }
+    for (var off = 0; off < padded.length; off += 64) {
+        for (var i = 0; i < 16; i++) {
+            w[i] = (padded[off + 4 * i] << 24) | (padded[off + 4 * i + 1] << 16) | (padded[off + 4 * i + 2] << 8) | padded[off + 4 * i + 3];
+        }
+        for (i = 16; i < 64; i++) {
+            var s0 = rotr(w[i - 15], 7) ^ rotr(w[i - 15], 18) ^ (w[i - 15] >>> 3);
+            var s1 = rotr(w[i - 2], 17) ^ rotr(w[i - 2], 19) ^ (w[i - 2] >>> 10);
+            w[i] = (w[i - 16] + s0 + w[i - 7] + s1) | 0;
+        }
+        var a = H[0], c = H[2], d = H[3], e = H[4], f = H[5], g = H[6], h = H[7], bb = H[1];
+        for (i = 0; i < 64; i++) {
+            var t1 = (h + (rotr(e, 6) ^ rotr(e, 11) ^ rotr(e, 25)) + ((e & f) ^ (~e & g)) + K[i] + w[i]) | 0;
+            var t2 = ((rotr(a, 2) ^ rotr(a, 13) ^ rotr(a, 22)) + ((a & bb) ^ (a & c) ^ (bb & c))) | 0;
+            h = g; g = f; f = e; e = (d + t1) | 0; d = c; c = bb; bb = a; a = (t1 + t2) | 0;
+        }
+        H[0] = (H[0] + a) | 0; H[1] = (H[1] + bb) | 0; H[2] = (H[2] + c) | 0; H[3] = (H[3] + d) | 0;
+        H[4] = (H[4] + e) | 0; H[5] = (H[5] + f) | 0; H[6] = (H[6] + g) | 0; H[7] = (H[7] + h) | 0;
+    }
+    return H.map(function(x) { return ('00000000' + (x >>> 0).toString(16)).slice(-8); }).join('');
+}
+
+var crypto = {
+    getRandomValues: function(array) {
+        var integer = [Int8Array, Uint8Array, Uint8ClampedArray, Int16Array, Uint16Array, Int32Array, Uint32Array];
+        if (typeof BigInt64Array === 'function') { integer.push(BigInt64Array, BigUint64Array); }
+        if (!integer.some(function(T) { return array instanceof T; })) {
+            throw new TypeError('TypeMismatchError: getRandomValues requires an integer typed array');
+        }
+        if (array.byteLength > 65536) { throw new Error('QuotaExceededError: getRandomValues is limited to 65536 bytes'); }
+        var bytes = new Uint8Array(array.buffer, array.byteOffset, array.byteLength);
+        for (var i = 0; i < bytes.length; i += 4) {
+            var r = __rfox_random_u32();
+            for (var j = 0; j < 4 && i + j < bytes.length; j++) { bytes[i + j] = (r >>> (8 * j)) & 0xff; }
+        }
+        return array;
+    },
+    // Computed synchronously; the returned promise is already settled
+    subtle: {
+        digest: function(algorithm, data) {
+            try {
+                var name = String(algorithm && typeof algorithm === 'object' ? algorithm.name : algorithm).toUpperCase();
+                var bytes = __rfox_buffer_bytes(data);
+                var hex;
+                if (typeof __rfox_digest === 'function') {
+                    var latin1 = '';
+                    for (var i = 0; i < bytes.length; i += 8192) {
+                        latin1 += String.fromCharCode.apply(null, Array.prototype.slice.call(bytes.subarray(i, i + 8192)));
+                    }
+                    hex = __rfox_digest(name, latin1);
+                } else if (name === 'SHA-256') {
+                    hex = __rfox_sha256_hex(bytes);
+                } else {
+                    throw new Error('NotSupportedError: unsupported digest algorithm ' + name);
+                }
+                var out = new Uint8Array(hex.length / 2);
+                for (var k = 0; k < out.length; k++) { out[k] = parseInt(hex.substr(2 * k, 2), 16); }
+                return Promise.resolve(out.buffer);
+            } catch (err) {
+                return Promise.reject(err);
+            }
+        }
+    }
+};
+
+// structuredClone for plain objects, arrays, Dates, RegExps, Maps, Sets and
+// primitives; shared and circular references are preserved. Functions,
+// symbols (and so DOM elements) throw DataCloneError.
+function structuredClone(value) {
+    var seen = new Map();
+    function clone(v) {
+        if (typeof v === 'function' || typeof v === 'symbol') {
+            throw new Error('DataCloneError: ' + typeof v + ' values could not be cloned');
+        }
+        if (v === null || typeof v !== 'object') { return v; }
+        if (seen.has(v)) { return seen.get(v); }
+        var out;
+        if (v instanceof Date) { out = new Date(v.getTime()); seen.set(v, out); return out; }
+        if (v instanceof RegExp) { out = new RegExp(v.source, v.flags); seen.set(v, out); return out; }
+        if (v instanceof Map) {
+            out = new Map(); seen.set(v, out);
+            v.forEach(function(val, key) { out.set(clone(key), clone(val)); });
+            return out;
+        }
+        if (v instanceof Set) {
+            out = new Set(); seen.set(v, out);
+            v.forEach(function(val) { out.add(clone(val)); });
+            return out;
+        }
+        out = Array.isArray(v) ? new Array(v.length) : {};
+        seen.set(v, out);
+        var keys = Object.keys(v);
+        for (var i = 0; i < keys.length; i++) { out[keys[i]] = clone(v[keys[i]]); }
+        return out;
+    }
+    return clone(value);
+}
+
+// Structural equality over the JSON-like values `structuredClone` copies
+// (Dates compare by time; object key order is ignored)
+function __rfox_deep_equal(a, b) {
+    if (a === b) { return a !== 0 || 1 / a === 1 / b; }
+    if (a !== a && b !== b) { return true; }
+    if (a === null || b === null || typeof a !== 'object' || typeof b !== 'object') { return false; }
+    if (a instanceof Date || b instanceof Date) {
+        return a instanceof Date && b instanceof Date && a.getTime() === b.getTime();
+    }
+    if (Array.isArray(a) !== Array.isArray(b)) { return false; }
+    var ka = Object.keys(a), kb = Object.keys(b);
+    if (ka.length !== kb.length) { return false; }
+    for (var i = 0; i < ka.length; i++) {
+        if (!Object.prototype.hasOwnProperty.call(b, ka[i]) || !__rfox_deep_equal(a[ka[i]], b[ka[i]])) { return false; }
+    }
+    return true;
+}
+
+// MutationObserver shim. The DOM snapshot never changes, so observers accept
+// `observe` calls but never queue records or invoke their callback.
+function MutationObserver(callback) {
+    if (!(this instanceof MutationObserver)) { throw new TypeError("Constructor MutationObserver requires 'new'"); }
+    if (typeof callback !== 'function') { throw new TypeError('MutationObserver callback must be a function'); }
+    this._callback = callback;
+}
+MutationObserver.prototype.observe = function(target, options) {
+    if (!target || typeof target !== 'object') { throw new TypeError('MutationObserver.observe target must be a node'); }
+    var o = options || {};
+    if (!o.childList && !o.attributes && !o.characterData && o.attributeFilter === undefined && o.attributeOldValue === undefined && o.characterDataOldValue === undefined) {
+        throw new TypeError('MutationObserver.observe options must enable childList, attributes or characterData');
+    }
+};
+MutationObserver.prototype.disconnect = function() {};
+MutationObserver.prototype.takeRecords = function() { return []; };
+
+// Synchronous DOM-style events. Nothing fires on its own: listeners only run
+// when a script calls `dispatchEvent`, which walks capture listeners from
+// `window` down to the target, then the target, then bubbles back up through
+// `parentElement`, `document` and `window` when the event `bubbles`.
+function Event(type, init) {
+    if (!(this instanceof Event)) { throw new TypeError("Constructor Event requires 'new'"); }
+    if (arguments.length === 0) { throw new TypeError('Event requires a type'); }
+    init = init || {};
+    this.type = String(type);
+    this.bubbles = !!init.bubbles;
+    this.cancelable = !!init.cancelable;
+    this.defaultPrevented = false;
+    this.target = null;
+    this.currentTarget = null;
+    this.eventPhase = 0;
+    this.timeStamp = __rfox_now;
+    this.isTrusted = false;
+    Object.defineProperty(this, '__rfox_stop', { value: 0, writable: true });
+}
+Event.NONE = 0; Event.CAPTURING_PHASE = 1; Event.AT_TARGET = 2; Event.BUBBLING_PHASE = 3;
+Event.prototype.preventDefault = function() { if (this.cancelable) this.defaultPrevented = true; };
+// 1 stops after the current target, 2 also skips its remaining listeners
+Event.prototype.stopPropagation = function() { this.__rfox_stop = Math.max(this.__rfox_stop, 1); };
+Event.prototype.stopImmediatePropagation = function() { this.__rfox_stop = 2; };
+Event.prototype.composedPath = function() { return this.__rfox_path ? this.__rfox_path.slice() : []; };
+
+function CustomEvent(type, init) {
+    if (!(this instanceof CustomEvent)) { throw new TypeError("Constructor CustomEvent requires 'new'"); }
+    Event.call(this, type, init);
+    this.detail = (init && init.detail !== undefined) ? init.detail : null;
+}
+CustomEvent.prototype = Object.create(Event.prototype);
+CustomEvent.prototype.constructor = CustomEvent;
+
+// Listeners are kept off the enumerable properties so snapshots skip them
+function __rfox_listeners(target) {
+    if (!Object.prototype.hasOwnProperty.call(target, '__rfox_listeners')) {
+        Object.defineProperty(target, '__rfox_listeners', { value: {}, configurable: true });
+    }
+    return target.__rfox_listeners;
+}
+
+function __rfox_add_listener(target, type, fn, options) {
+    if (!fn || (typeof fn !== 'function' && typeof fn.handleEvent !== 'function')) return;
+    var capture = (typeof options === 'boolean') ? options : !!(options && options.capture);
+    var once = !!(options && typeof options === 'object' && options.once);
+    var list = __rfox_listeners(target)[type] || (__rfox_listeners(target)[type] = []);
+    for (var i=0;i<list.length;i++) { if (list[i].fn === fn && list[i].capture === capture) return; }
+    list.push({ fn: fn, capture: capture, once: once });
+}
+
+function __rfox_remove_listener(target, type, fn, options) {
+    var capture = (typeof options === 'boolean') ? options : !!(options && options.capture);
+    var list = __rfox_listeners(target)[type] || [];
+    for (var i=0;i<list.length;i++) {
+        if (list[i].fn === fn && list[i].capture === capture) { list[i].removed = true; list.splice(i, 1); return; }
+    }
+}
+
+// Run `target`'s listeners for `event` in the current phase; a throwing
+// listener is reported and doesn't stop the others
+function __rfox_invoke_listeners(target, event, capture) {
+    var list = (__rfox_listeners(target)[event.type] || []).slice();
+    event.currentTarget = target;
+    for (var i=0;i<list.length;i++) {
+        var l = list[i];
+        if (l.removed || (event.eventPhase !== Event.AT_TARGET && l.capture !== capture)) continue;
+        if (l.once) __rfox_remove_listener(target, event.type, l.fn, l.capture);
+        try {
+            if (typeof l.fn === 'function') l.fn.call(target, event); else l.fn.handleEvent(event);
+        } catch (e) {
+            console.error('Uncaught ' + String(e));
+        }
+        if (event.__rfox_stop === 2) break;
+    }
+}
+
+// `target` and its ancestors up to `window`, innermost first
+function __rfox_event_path(target) {
+    var path = [target];
+    if (target === window) return path;
+    if (target !== document) {
+        var node = target;
+        while ((node = __rfox_parent_of(node))) path.push(node);
+        var root = path[path.length - 1];
+        // Detached subtrees don't reach the document
+        if (__rfox_dom.indexOf(root) === -1) return path;
+        path.push(document);
+    }
+    path.push(window);
+    return path;
+}
+
+function __rfox_dispatch(target, event) {
+    if (!(event instanceof Event)) { throw new TypeError('dispatchEvent requires an Event'); }
+    var path = __rfox_event_path(target);
+    event.target = target;
+    event.__rfox_stop = 0;
+    Object.defineProperty(event, '__rfox_path', { value: path, configurable: true });
+    event.eventPhase = Event.CAPTURING_PHASE;
+    for (var i=path.length-1;i>0 && !event.__rfox_stop;i--) __rfox_invoke_listeners(path[i], event, true);
+    if (!event.__rfox_stop) {
+        event.eventPhase = Event.AT_TARGET;
+        __rfox_invoke_listeners(target, event, false);
+    }
+    event.eventPhase = Event.BUBBLING_PHASE;
+    for (var j=1;j<path.length && event.bubbles && !event.__rfox_stop;j++) __rfox_invoke_listeners(path[j], event, false);
+    event.eventPhase = Event.NONE;
+    event.currentTarget = null;
+    return !event.defaultPrevented;
+}
+
+document.addEventListener = function(type, fn, options) { __rfox_add_listener(document, type, fn, options); };
+document.removeEventListener = function(type, fn, options) { __rfox_remove_listener(document, type, fn, options); };
+document.dispatchEvent = function(event) { return __rfox_dispatch(document, event); };
+window.addEventListener = function(type, fn, options) { __rfox_add_listener(window, type, fn, options); };
+window.removeEventListener = function(type, fn, options) { __rfox_remove_listener(window, type, fn, options); };
+window.dispatchEvent = function(event) { return __rfox_dispatch(window, event); };
+
+function __rfox_escape_html(s, attr) {
+    s = String(s).replace(/&/g, '&amp;').replace(/</g, '&lt;').replace(/>/g, '&gt;');
+    return attr ? s.replace(/"/g, '&quot;') : s;
+}
+
+// Markup for element `el`, at index `idx` of the snapshot
+function __rfox_outer_html(el, idx) {
+    var out = '<' + el.tag;
+    for (var a=0;a<el.attributes.length;a++) {
+        out += ' ' + el.attributes[a][0] + '="' + __rfox_escape_html(el.attributes[a][1], true) + '"';
+    }
+    out += '>';
+    if (__rfox_void_tags[el.tag]) return out;
+    if (el.html !== undefined) {
+        out += el.html;
+    } else {
+        var inner = '';
+        for (var j=idx+1;j<__rfox_dom.length;j++) {
+            var node = __rfox_dom[j];
+            if (node && node.parent === idx) inner += __rfox_outer_html(node, j);
+        }
+        out += inner || __rfox_escape_html(el.text || '');
+    }
+    return out + '</' + el.tag + '>';
+}
+
+// What scripts changed in the page's own elements, for the engine to write
+// back into the page source: `removed`, `content` (markup replacing the
+// children), `attributes`, and `append` (markup of elements appended).
+// Elements a lazy snapshot never fetched are unchanged.
+function __rfox_dom_changes() {
+    var changes = [];
+    for (var i=0;i<__rfox_snapshot_len;i++) {
+        var el = __rfox_dom[i];
+        if (el === undefined) continue;
+        if (el === null) { changes.push({ i: i, removed: true }); continue; }
+        var change = { i: i };
+        if (el.__rfox_content !== undefined) change.content = el.__rfox_content;
+        if (el.__rfox_attrs_changed) change.attributes = el.attributes;
+        var append = '';
+        for (var j=__rfox_snapshot_len;j<__rfox_dom.length;j++) {
+            var node = __rfox_dom[j];
+            if (node && node.parent === i && !node.__rfox_from_markup) append += __rfox_outer_html(node, j);
+        }
+        if (append) change.append = append;
+        if (change.content !== undefined || change.attributes || append) changes.push(change);
+    }
+    return changes;
+}
+
+// Called by the engine once a page worker's harness and init scripts have run
+function __rfox_fire_load_events() {
+    document.dispatchEvent(new Event('DOMContentLoaded', { bubbles: true }));
+    window.dispatchEvent(new Event('load'));
+}
+
+// Called by the engine after the load events with `run_until_idle_on_load`:
+// run the tasks the page scheduled while loading, moving the logical clock
+// forward to each pending timer, for at most `max_event_loop_steps` rounds
+// (with a warning when timers are still pending then).
+// Returns the title scripts left behind and the DOM changes they made.
+function __rfox_settle_after_load() {
+    var rounds = 0;
+    while (true) {
+        __rfox_run_until_idle();
+        if (!__rfox_macrotasks.length) break;
+        if (++rounds >= __rfox_max_event_loop_steps) {
+            console.warn('__rfox_settle_after_load: stopped after ' + rounds + ' rounds with timers still pending (max_event_loop_steps)');
+            break;
+        }
+        var next = __rfox_macrotasks[0].due;
+        for (var i = 1; i < __rfox_macrotasks.length; i++) { next = Math.min(next, __rfox_macrotasks[i].due); }
+        __rfox_now = Math.max(__rfox_now, next);
+    }
+    return { title: String(document.title), changes: __rfox_dom_changes() };
+}
+
+// Report an error thrown by a page, init or engine-wrapped script
+function __rfox_report_error(e) {
+    console.error(e);
+}
+
+// Public API, installed only for the names `script_global_allowlist` allows so
+// the rest are never defined for scripts. A harness global has to be listed
+// here to be visible at all.
+var __rfox_public_api = {
+    document: document,
+    window: window,
+    console: console,
+    querySelector: querySelector,
+    querySelectorAll: querySelectorAll,
+    getComputedStyle: getComputedStyle,
+    normalizeColor: normalizeColor,
+    normalizeUnit: normalizeUnit,
+    queueMicrotask: queueMicrotask,
+    setTimeout: setTimeout,
+    clearTimeout: clearTimeout,
+    setInterval: setInterval,
+    clearInterval: clearInterval,
+    btoa: btoa,
+    atob: atob,
+    TextEncoder: TextEncoder,
+    TextDecoder: TextDecoder,
+    URL: URL,
+    URLSearchParams: URLSearchParams,
+    location: location,
+    crypto: crypto,
+    structuredClone: structuredClone,
+    MutationObserver: MutationObserver,
+    requestAnimationFrame: requestAnimationFrame,
+    cancelAnimationFrame: cancelAnimationFrame,
+    Event: Event,
+    CustomEvent: CustomEvent
+};
+for (var __rfox_name in __rfox_public_api) {
+    if (__rfox_global_allowed(__rfox_name)) globalThis[__rfox_name] = __rfox_public_api[__rfox_name];
+}
+
+// Entry points the engine (and host-side tests) call, whatever the allowlist
+var __rfox_entry_points = {
+    __rfox_console: __rfox_console,
+    __rfox_dom: __rfox_dom,
+    __rfox_snapshot: __rfox_snapshot,
+    __rfox_explain_style: __rfox_explain_style,
+    __rfox_run_until_idle: __rfox_run_until_idle,
+    __rfox_tick: __rfox_tick,
+    __rfox_deep_equal: __rfox_deep_equal,
+    __rfox_fire_load_events: __rfox_fire_load_events,
+    __rfox_settle_after_load: __rfox_settle_after_load,
+    __rfox_report_error: __rfox_report_error
+};
+for (var __rfox_entry in __rfox_entry_points) {
+    globalThis[__rfox_entry] = __rfox_entry_points[__rfox_entry];
+}
+
+})();
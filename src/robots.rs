@@ -0,0 +1,166 @@
+//! robots.txt parsing and matching for `EngineConfig::respect_robots_txt`
+//!
+//! Follows RFC 9309: the group whose `User-agent` token appears in the
+//! engine's user agent (longest token wins, `*` otherwise) applies, the
+//! longest matching `Allow`/`Disallow` path decides and `Allow` wins ties.
+//! Paths support the `*` wildcard and a trailing `$` anchor.
+
+// User-agent tokens of a group and its (allow, path pattern) rules
+type Group = (Vec<String>, Vec<(bool, String)>);
+
+/// Rules of the group that applies to one user agent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Robots {
+    // (allow, path pattern)
+    rules: Vec<(bool, String)>,
+    disallow_all: bool,
+}
+
+impl Robots {
+    /// Everything is allowed (missing or unavailable robots.txt).
+    pub(crate) fn allow_all() -> Self {
+        Self::default()
+    }
+
+    /// Nothing is allowed (robots.txt unreachable because of a server error).
+    pub(crate) fn disallow_all() -> Self {
+        Self {
+            rules: Vec::new(),
+            disallow_all: true,
+        }
+    }
+
+    /// Parse `body` and keep the group that applies to `user_agent`.
+    pub(crate) fn parse(body: &str, user_agent: &str) -> Self {
+        let ua = user_agent.to_ascii_lowercase();
+        // Consecutive user-agent lines share one group
+        let mut groups: Vec<Group> = Vec::new();
+        let mut in_agents = false;
+        for line in body.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim().to_ascii_lowercase().as_str() {
+                "user-agent" => {
+                    if !in_agents {
+                        groups.push((Vec::new(), Vec::new()));
+                        in_agents = true;
+                    }
+                    if let Some(group) = groups.last_mut() {
+                        group.0.push(value.to_ascii_lowercase());
+                    }
+                }
+                key @ ("allow" | "disallow") => {
+                    in_agents = false;
+                    // An empty `Disallow:` allows everything and adds no rule
+                    if let (Some(group), false) = (groups.last_mut(), value.is_empty()) {
+                        group.1.push((key == "allow", value.to_string()));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let specific = groups
+            .iter()
+            .flat_map(|(agents, rules)| agents.iter().map(move |a| (a, rules)))
+            .filter(|(agent, _)| agent.as_str() != "*" && ua.contains(agent.as_str()))
+            .max_by_key(|(agent, _)| agent.len())
+            .map(|(agent, _)| agent.clone());
+        let wanted = specific.unwrap_or_else(|| "*".to_string());
+        // Groups naming the same agent are merged
+        let rules = groups
+            .into_iter()
+            .filter(|(agents, _)| agents.contains(&wanted))
+            .flat_map(|(_, rules)| rules)
+            .collect();
+        Self {
+            rules,
+            disallow_all: false,
+        }
+    }
+
+    /// Whether `path` (path plus query, starting with `/`) may be fetched.
+    pub(crate) fn is_allowed(&self, path: &str) -> bool {
+        if self.disallow_all {
+            return false;
+        }
+        // Longest pattern wins; `Allow` sorts after `Disallow` on ties
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| path_matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .is_none_or(|(allow, _)| *allow)
+    }
+}
+
+// Match a robots.txt path pattern (`*` wildcard, optional trailing `$`)
+// against the start of `path`.
+fn path_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(p) => (p, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or("");
+    let Some(mut rest) = path.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    for (i, part) in parts.iter().enumerate() {
+        let last = i + 1 == parts.len();
+        if last && anchored {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(pos) => rest = &rest[pos + part.len()..],
+            None => return false,
+        }
+    }
+    !anchored || rest.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROBOTS: &str = "\
+# example
+User-agent: *
+Disallow: /private
+Allow: /private/open
+Disallow: /*.pdf$
+
+User-agent: OtherBot
+User-agent: RFOX
+Disallow: /rfox-only
+";
+
+    #[test]
+    fn test_generic_group_applies_with_longest_match() {
+        let robots = Robots::parse(ROBOTS, "Mozilla/5.0 Firefox/115.0");
+        assert!(robots.is_allowed("/"));
+        assert!(!robots.is_allowed("/private"));
+        assert!(!robots.is_allowed("/private/page?x=1"));
+        assert!(robots.is_allowed("/private/open/page"));
+        assert!(!robots.is_allowed("/files/report.pdf"));
+        assert!(robots.is_allowed("/files/report.pdf?download=1"));
+        assert!(robots.is_allowed("/rfox-only"));
+    }
+
+    #[test]
+    fn test_user_agent_group_replaces_generic_one() {
+        let robots = Robots::parse(ROBOTS, "Mozilla/5.0 Gecko Firefox/115.0 RFOX/0.3");
+        assert!(!robots.is_allowed("/rfox-only/x"));
+        assert!(robots.is_allowed("/private"));
+    }
+
+    #[test]
+    fn test_empty_and_unavailable() {
+        assert!(Robots::parse("User-agent: *\nDisallow:\n", "x").is_allowed("/a"));
+        assert!(Robots::allow_all().is_allowed("/a"));
+        assert!(!Robots::disallow_all().is_allowed("/"));
+    }
+}
@@ -0,0 +1,146 @@
+//! Thread-shareable engine handle
+//!
+//! Backends own blocking HTTP clients, worker channels and (for CDP) a browser
+//! connection, none of which are meant to be driven from several threads at
+//! once. `SharedEngine` wraps any backend behind a single lock so it can be
+//! cloned into worker threads without users hand-rolling `Arc<Mutex<_>>`.
+
+use crate::{Cookie, CookieParam, Engine, Error, Result, ScriptResult, TextSnapshot};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// A cloneable, `Send + Sync` handle to a boxed `Engine`.
+///
+/// Every method locks the inner engine for the duration of the call, so
+/// operations issued from different threads are serialized: a slow `load_url`
+/// on one thread blocks an `eval` on another until it returns. Callbacks
+/// (`on_load`, `on_console`, ...) must be registered on the engine before it
+/// is wrapped, since those methods are not available through `dyn Engine`.
+///
+/// The engine is cleaned up when the last handle is dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// # fn main() -> rfheadless::Result<()> {
+/// let engine = rfheadless::new_engine(rfheadless::EngineConfig::default())?;
+/// let shared = rfheadless::SharedEngine::new(engine);
+/// shared.load_url("https://example.com")?;
+///
+/// let worker = shared.clone();
+/// std::thread::spawn(move || worker.eval("document.title")).join().unwrap()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct SharedEngine(Arc<Mutex<Box<dyn Engine + Send>>>);
+
+impl SharedEngine {
+    /// Wrap an engine instance for shared use across threads.
+    pub fn new<E>(engine: E) -> Self
+    where
+        E: Engine + Send + 'static,
+    {
+        Self::from_boxed(Box::new(engine))
+    }
+
+    /// Wrap an already boxed engine.
+    pub fn from_boxed(engine: Box<dyn Engine + Send>) -> Self {
+        Self(Arc::new(Mutex::new(engine)))
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, Box<dyn Engine + Send>>> {
+        self.0
+            .lock()
+            .map_err(|_| Error::Other("Shared engine lock poisoned".into()))
+    }
+
+    /// Run `f` with exclusive access to the underlying engine. Useful for
+    /// operations that don't have a dedicated convenience method.
+    pub fn with<R>(&self, f: impl FnOnce(&mut dyn Engine) -> R) -> Result<R> {
+        let mut guard = self.lock()?;
+        Ok(f(&mut **guard))
+    }
+
+    /// Load a URL (see `Engine::load_url`)
+    pub fn load_url(&self, url: &str) -> Result<()> {
+        self.lock()?.load_url(url)
+    }
+
+    /// Evaluate a script (see `Engine::evaluate_script`)
+    pub fn eval(&self, script: &str) -> Result<ScriptResult> {
+        self.lock()?.evaluate_script(script)
+    }
+
+    /// Evaluate a script directly in the page (see `Engine::evaluate_script_in_page`)
+    pub fn eval_in_page(&self, script: &str) -> Result<ScriptResult> {
+        self.lock()?.evaluate_script_in_page(script)
+    }
+
+    /// Render a text snapshot of the current page
+    pub fn render_text_snapshot(&self) -> Result<TextSnapshot> {
+        self.lock()?.render_text_snapshot()
+    }
+
+    /// Render the current page as PNG bytes
+    pub fn render_png(&self) -> Result<Vec<u8>> {
+        self.lock()?.render_png()
+    }
+
+    /// Get cookies for the current page
+    pub fn get_cookies(&self) -> Result<Vec<Cookie>> {
+        self.lock()?.get_cookies()
+    }
+
+    /// Set cookies on the current page
+    pub fn set_cookies(&self, cookies: Vec<CookieParam>) -> Result<()> {
+        self.lock()?.set_cookies(cookies)
+    }
+}
+
+#[cfg(all(test, feature = "rfengine"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shared_engine_eval_from_two_threads() {
+        // Skip on CI where network may not be available
+        if std::env::var("CI").is_ok() {
+            return;
+        }
+
+        let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+        let addr = server.server_addr();
+        std::thread::spawn(move || {
+            if let Ok(request) = server.recv() {
+                let response = tiny_http::Response::from_string(
+                    "<html><head><title>Shared</title></head><body>S</body></html>",
+                );
+                let _ = request.respond(response);
+            }
+        });
+
+        let engine = crate::rfengine::RFEngine::new(crate::EngineConfig::default())
+            .expect("Failed to create RFEngine");
+        let shared = SharedEngine::new(engine);
+        shared
+            .load_url(&format!("http://{}", addr))
+            .expect("Failed to load URL");
+
+        let handles: Vec<_> = ["1 + 1", "document.title"]
+            .iter()
+            .map(|script| {
+                let s = shared.clone();
+                let script = script.to_string();
+                std::thread::spawn(move || s.eval(&script))
+            })
+            .collect();
+        let results: Vec<ScriptResult> = handles
+            .into_iter()
+            .map(|h| h.join().expect("thread panicked").expect("eval failed"))
+            .collect();
+
+        assert!(results.iter().all(|r| !r.is_error));
+        assert!(results[0].value.contains('2'));
+        assert!(results[1].value.contains("Shared"));
+    }
+}
@@ -5,6 +5,8 @@
 //! exposes the same `Engine` trait as other backends. JavaScript and
 //! screenshot support are not provided by this engine (planned for later).
 
+#[cfg(not(feature = "rfengine"))]
+use crate::Error;
 use crate::{Engine, EngineConfig, Result, ScriptResult, TextSnapshot};
 #[cfg(not(feature = "rfengine"))]
 use reqwest::blocking::Client;
@@ -23,6 +25,8 @@ type OnLoadHandler = Arc<dyn Fn(&TextSnapshot) + Send + Sync>;
 type OnConsoleHandler = Arc<dyn Fn(&crate::ConsoleMessage) + Send + Sync>;
 #[cfg(not(feature = "rfengine"))]
 type OnRequestHandler = Arc<dyn Fn(&crate::RequestInfo) -> crate::RequestAction + Send + Sync>;
+#[cfg(not(feature = "rfengine"))]
+type OnProgressHandler = Arc<dyn Fn(crate::LoadProgress) + Send + Sync>;
 
 /// A simple, dependency-light engine that does not run JavaScript.
 ///
@@ -52,6 +56,9 @@ pub struct SimpleEngine {
 
     #[cfg(not(feature = "rfengine"))]
     on_request: Option<OnRequestHandler>,
+
+    #[cfg(not(feature = "rfengine"))]
+    on_progress: Option<OnProgressHandler>,
 }
 
 impl Engine for SimpleEngine {
@@ -85,6 +92,7 @@ impl Engine for SimpleEngine {
             on_load: None,
             on_console: None,
             on_request: None,
+            on_progress: None,
         })
     }
 
@@ -96,16 +104,30 @@ impl Engine for SimpleEngine {
 
         #[cfg(not(feature = "rfengine"))]
         {
+            let progress = |phase: &str, bytes_loaded: u64| {
+                if let Some(cb) = &self.on_progress {
+                    cb(crate::LoadProgress {
+                        phase: phase.to_string(),
+                        bytes_loaded,
+                    });
+                }
+            };
+            progress("fetch_started", 0);
             let res = self
                 .client
                 .get(url)
                 .header("User-Agent", self.config.user_agent.clone())
                 .send()
-                .map_err(|e| Error::LoadError(format!("HTTP GET failed: {}", e)))?;
-
-            let body = res
-                .text()
-                .map_err(|e| Error::LoadError(format!("Failed to read response body: {}", e)))?;
+                .map_err(|e| Error::LoadError {
+                    url: url.to_string(),
+                    reason: format!("HTTP GET failed: {}", e),
+                })?;
+
+            let body = res.text().map_err(|e| Error::LoadError {
+                url: url.to_string(),
+                reason: format!("Failed to read response body: {}", e),
+            })?;
+            progress("body_received", body.len() as u64);
 
             self.last_html = Some(body);
             self.last_url = Some(url.to_string());
@@ -121,6 +143,27 @@ impl Engine for SimpleEngine {
         }
     }
 
+    fn load_html(&mut self, html: &str, base_url: Option<&str>) -> Result<()> {
+        #[cfg(feature = "rfengine")]
+        {
+            self.inner.load_html(html, base_url)
+        }
+
+        #[cfg(not(feature = "rfengine"))]
+        {
+            self.last_html = Some(html.to_string());
+            self.last_url = Some(base_url.unwrap_or("about:blank").to_string());
+
+            if let Some(cb) = &self.on_load {
+                if let Ok(snapshot) = self.render_text_snapshot() {
+                    cb(&snapshot);
+                }
+            }
+
+            Ok(())
+        }
+    }
+
     fn render_text_snapshot(&self) -> Result<TextSnapshot> {
         #[cfg(feature = "rfengine")]
         {
@@ -150,10 +193,14 @@ impl Engine for SimpleEngine {
                 .map(|b| b.text().collect::<String>())
                 .unwrap_or_default();
 
+            let detected_language =
+                crate::lang::detect_language(document.root_element().value().attr("lang"), &text);
+
             Ok(TextSnapshot {
                 title,
                 text,
                 url: self.last_url.clone().unwrap_or_default(),
+                detected_language,
             })
         }
     }
@@ -165,9 +212,7 @@ impl Engine for SimpleEngine {
         }
         #[cfg(not(feature = "rfengine"))]
         {
-            Err(Error::RenderError(
-                "Screenshots are not supported by SimpleEngine".into(),
-            ))
+            Err(Error::unsupported("render_png", "SimpleEngine"))
         }
     }
 
@@ -178,9 +223,7 @@ impl Engine for SimpleEngine {
         }
         #[cfg(not(feature = "rfengine"))]
         {
-            Err(Error::ScriptError(
-                "JavaScript execution is not supported by SimpleEngine".into(),
-            ))
+            Err(Error::unsupported("evaluate_script", "SimpleEngine"))
         }
     }
 
@@ -191,12 +234,23 @@ impl Engine for SimpleEngine {
         }
         #[cfg(not(feature = "rfengine"))]
         {
-            Err(Error::ScriptError(
-                "JavaScript execution is not supported by SimpleEngine".into(),
+            Err(Error::unsupported(
+                "evaluate_script_in_page",
+                "SimpleEngine",
             ))
         }
     }
 
+    #[cfg(feature = "rfengine")]
+    fn evaluate_script_timeout(&mut self, script: &str, timeout_ms: u64) -> Result<ScriptResult> {
+        self.inner.evaluate_script_timeout(script, timeout_ms)
+    }
+
+    #[cfg(feature = "rfengine")]
+    fn evaluate_module(&mut self, source: &str) -> Result<ScriptResult> {
+        self.inner.evaluate_module(source)
+    }
+
     fn on_load<F>(&mut self, cb: F)
     where
         F: Fn(&TextSnapshot) + Send + Sync + 'static,
@@ -272,11 +326,40 @@ impl Engine for SimpleEngine {
         }
     }
 
+    fn on_progress<F>(&mut self, cb: F)
+    where
+        F: Fn(crate::LoadProgress) + Send + Sync + 'static,
+    {
+        #[cfg(feature = "rfengine")]
+        {
+            self.inner.on_progress(cb);
+        }
+        #[cfg(not(feature = "rfengine"))]
+        {
+            self.on_progress = Some(Arc::new(cb));
+        }
+    }
+
+    fn clear_on_progress(&mut self) {
+        #[cfg(feature = "rfengine")]
+        {
+            self.inner.clear_on_progress();
+        }
+        #[cfg(not(feature = "rfengine"))]
+        {
+            self.on_progress = None;
+        }
+    }
+
     fn get_cookies(&self) -> Result<Vec<crate::Cookie>> {
         // SimpleEngine does not manage cookies; return empty set
         Ok(vec![])
     }
 
+    fn get_cookies_for_url(&self, _url: &str) -> Result<Vec<crate::Cookie>> {
+        Ok(vec![])
+    }
+
     fn set_cookies(&mut self, _cookies: Vec<crate::CookieParam>) -> Result<()> {
         // No-op for now
         Ok(())
@@ -303,6 +386,154 @@ impl Engine for SimpleEngine {
         }
     }
 
+    fn extract_jsonld(&self) -> Result<Vec<serde_json::Value>> {
+        #[cfg(feature = "rfengine")]
+        {
+            self.inner.extract_jsonld()
+        }
+        #[cfg(not(feature = "rfengine"))]
+        {
+            let html = self
+                .last_html
+                .as_ref()
+                .ok_or_else(|| Error::RenderError("No document loaded".into()))?;
+            Ok(crate::extract::jsonld(&Html::parse_document(html)))
+        }
+    }
+
+    fn extract_microdata(&self) -> Result<serde_json::Value> {
+        #[cfg(feature = "rfengine")]
+        {
+            self.inner.extract_microdata()
+        }
+        #[cfg(not(feature = "rfengine"))]
+        {
+            let html = self
+                .last_html
+                .as_ref()
+                .ok_or_else(|| Error::RenderError("No document loaded".into()))?;
+            Ok(crate::extract::microdata(&Html::parse_document(html)))
+        }
+    }
+
+    fn extract_table(
+        &mut self,
+        selector: &str,
+    ) -> Result<Vec<std::collections::HashMap<String, String>>> {
+        #[cfg(feature = "rfengine")]
+        {
+            self.inner.extract_table(selector)
+        }
+        #[cfg(not(feature = "rfengine"))]
+        {
+            let html = self
+                .last_html
+                .as_ref()
+                .ok_or_else(|| Error::RenderError("No document loaded".into()))?;
+            crate::extract::table_records(&Html::parse_document(html), selector)
+        }
+    }
+
+    fn extract_feeds(&self) -> Result<Vec<String>> {
+        #[cfg(feature = "rfengine")]
+        {
+            self.inner.extract_feeds()
+        }
+        #[cfg(not(feature = "rfengine"))]
+        {
+            let html = self
+                .last_html
+                .as_ref()
+                .ok_or_else(|| Error::RenderError("No document loaded".into()))?;
+            let page_url = self.last_url.as_deref().unwrap_or("about:blank");
+            Ok(crate::extract::feed_links(
+                &Html::parse_document(html),
+                page_url,
+            ))
+        }
+    }
+
+    fn find_next_page(&self) -> Result<Option<String>> {
+        #[cfg(feature = "rfengine")]
+        {
+            self.inner.find_next_page()
+        }
+        #[cfg(not(feature = "rfengine"))]
+        {
+            let html = self
+                .last_html
+                .as_ref()
+                .ok_or_else(|| Error::RenderError("No document loaded".into()))?;
+            let page_url = self.last_url.as_deref().unwrap_or("about:blank");
+            Ok(crate::extract::next_page(
+                &Html::parse_document(html),
+                page_url,
+            ))
+        }
+    }
+
+    fn get_html(&self) -> Result<String> {
+        #[cfg(feature = "rfengine")]
+        {
+            self.inner.get_html()
+        }
+        #[cfg(not(feature = "rfengine"))]
+        {
+            self.last_html
+                .clone()
+                .ok_or_else(|| Error::RenderError("No document loaded".into()))
+        }
+    }
+
+    fn content_hash(&self) -> Result<String> {
+        #[cfg(feature = "rfengine")]
+        {
+            self.inner.content_hash()
+        }
+        #[cfg(not(feature = "rfengine"))]
+        {
+            if self.config.content_hash_html {
+                let html = self
+                    .last_html
+                    .as_ref()
+                    .ok_or_else(|| Error::RenderError("No document loaded".into()))?;
+                return Ok(crate::sha256_hex(html.as_bytes()));
+            }
+            let snapshot = self.render_text_snapshot()?;
+            Ok(crate::sha256_hex(snapshot.normalized_content().as_bytes()))
+        }
+    }
+
+    #[cfg(feature = "rfengine")]
+    fn reload_if_changed(&mut self) -> Result<bool> {
+        self.inner.reload_if_changed()
+    }
+
+    #[cfg(feature = "rfengine")]
+    fn add_init_script(&mut self, source: &str) -> Result<crate::ScriptId> {
+        self.inner.add_init_script(source)
+    }
+
+    #[cfg(feature = "rfengine")]
+    fn remove_init_script(&mut self, id: crate::ScriptId) -> Result<()> {
+        self.inner.remove_init_script(id)
+    }
+
+    fn query_xpath(&mut self, xpath: &str) -> Result<Vec<String>> {
+        #[cfg(feature = "rfengine")]
+        {
+            self.inner.query_xpath(xpath)
+        }
+        #[cfg(not(feature = "rfengine"))]
+        {
+            let html = self
+                .last_html
+                .as_ref()
+                .ok_or_else(|| Error::RenderError("No document loaded".into()))?;
+            crate::xpath::query(&Html::parse_document(html), xpath)
+        }
+    }
+
     fn close(self) -> Result<()> {
         #[cfg(feature = "rfengine")]
         {
@@ -349,4 +580,22 @@ mod tests {
         assert!(snapshot.title.contains("Hi"));
         assert!(snapshot.text.contains("Hello"));
     }
+
+    #[cfg(not(feature = "rfengine"))]
+    #[test]
+    fn test_unsupported_operations() {
+        let mut engine = SimpleEngine::new(crate::EngineConfig::default())
+            .expect("Failed to create SimpleEngine");
+        match engine.render_png() {
+            Err(Error::Unsupported { operation, backend }) => {
+                assert_eq!(operation, "render_png");
+                assert_eq!(backend, "SimpleEngine");
+            }
+            other => panic!("expected Unsupported, got {:?}", other),
+        }
+        assert!(matches!(
+            engine.evaluate_script("1"),
+            Err(Error::Unsupported { .. })
+        ));
+    }
 }
@@ -0,0 +1,162 @@
+//! XML sitemap parsing for `RFEngine::fetch_sitemap`
+//!
+//! Handles both `<urlset>` sitemaps and `<sitemapindex>` files that list
+//! further sitemaps, gzip-compressed or not. The format is simple enough that
+//! `<loc>` values are scanned for directly instead of building an XML tree;
+//! CDATA sections and character entities are understood.
+
+use std::io::Read;
+
+// Sitemaps are capped at 50 MiB uncompressed by the protocol
+const MAX_SITEMAP_BYTES: u64 = 50 * 1024 * 1024;
+
+/// Levels of `<sitemapindex>` followed below the requested sitemap. The
+/// protocol only allows one, but nested indexes show up in the wild.
+pub(crate) const MAX_INDEX_DEPTH: usize = 3;
+
+/// `<loc>` values of one sitemap file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Sitemap {
+    /// Page URLs (`<urlset>`)
+    pub(crate) urls: Vec<String>,
+    /// Child sitemap URLs (`<sitemapindex>`)
+    pub(crate) sitemaps: Vec<String>,
+}
+
+/// Gunzip `body` when it starts with the gzip magic bytes; other bodies are
+/// returned unchanged.
+pub(crate) fn decompress(body: Vec<u8>) -> std::result::Result<Vec<u8>, String> {
+    if !body.starts_with(&[0x1f, 0x8b]) {
+        return Ok(body);
+    }
+    let mut out = Vec::new();
+    flate2::read::GzDecoder::new(body.as_slice())
+        .take(MAX_SITEMAP_BYTES)
+        .read_to_end(&mut out)
+        .map_err(|e| format!("Invalid gzip sitemap: {}", e))?;
+    Ok(out)
+}
+
+/// Collect the `<loc>` values of `xml`, as sitemap URLs when the root is a
+/// `<sitemapindex>` and as page URLs otherwise.
+pub(crate) fn parse(xml: &str) -> Sitemap {
+    let mut locs = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find("<loc>") {
+        rest = &rest[start + "<loc>".len()..];
+        let Some(end) = rest.find("</loc>") else {
+            break;
+        };
+        let loc = unescape(rest[..end].trim());
+        if !loc.is_empty() {
+            locs.push(loc);
+        }
+        rest = &rest[end + "</loc>".len()..];
+    }
+    if xml.contains("<sitemapindex") {
+        Sitemap {
+            urls: Vec::new(),
+            sitemaps: locs,
+        }
+    } else {
+        Sitemap {
+            urls: locs,
+            sitemaps: Vec::new(),
+        }
+    }
+}
+
+/// Text content of an XML element: a CDATA section as-is, otherwise with
+/// the predefined and numeric character entities decoded. Shared with feed
+/// parsing.
+pub(crate) fn unescape(text: &str) -> String {
+    if let Some(inner) = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|t| t.strip_suffix("]]>"))
+    {
+        return inner.trim().to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest
+            .find(';')
+            .and_then(|semi| Some((entity_char(&rest[1..semi])?, semi)));
+        match entity {
+            Some((c, semi)) => {
+                out.push(c);
+                rest = &rest[semi + 1..];
+            }
+            // Not an entity; keep the `&`
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn entity_char(name: &str) -> Option<char> {
+    let code = match name {
+        "lt" => return Some('<'),
+        "gt" => return Some('>'),
+        "amp" => return Some('&'),
+        "quot" => return Some('"'),
+        "apos" => return Some('\''),
+        _ => match name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+            Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+            None => name.strip_prefix('#')?.parse().ok()?,
+        },
+    };
+    char::from_u32(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_urlset_and_index() {
+        let urlset = r#"<?xml version="1.0" encoding="UTF-8"?>
+            <urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <url><loc>https://example.com/</loc><lastmod>2024-01-01</lastmod></url>
+              <url><loc> https://example.com/search?q=a&amp;page=2 </loc></url>
+              <url><loc><![CDATA[https://example.com/a&b]]></loc></url>
+              <url><loc>https://example.com/caf&#233;/&#x3b1;?x=&amp;y&z</loc></url>
+            </urlset>"#;
+        assert_eq!(
+            parse(urlset).urls,
+            [
+                "https://example.com/",
+                "https://example.com/search?q=a&page=2",
+                "https://example.com/a&b",
+                "https://example.com/café/α?x=&y&z",
+            ]
+        );
+
+        let index = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+              <sitemap><loc>https://example.com/posts.xml</loc></sitemap>
+            </sitemapindex>"#;
+        assert_eq!(
+            parse(index),
+            Sitemap {
+                urls: Vec::new(),
+                sitemaps: vec!["https://example.com/posts.xml".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_decompress_passes_plain_bodies_through() {
+        use std::io::Write;
+        let mut gz = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gz.write_all(b"<urlset/>").unwrap();
+        assert_eq!(decompress(gz.finish().unwrap()).unwrap(), b"<urlset/>");
+        assert_eq!(decompress(b"<urlset/>".to_vec()).unwrap(), b"<urlset/>");
+        assert!(decompress(vec![0x1f, 0x8b, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,209 @@
+//! Source-map resolution for console stack locations (feature: `sourcemap`)
+//!
+//! When a script ends with a `//# sourceMappingURL=` comment, stack frames
+//! reported against it point at generated (usually minified) positions. Maps
+//! are loaded once per script URL while the page's scripts are fetched, so
+//! rewriting `ConsoleMessage` frames to their original file/line/column never
+//! does I/O on the script worker.
+//!
+//! Boa does not fill in `Error.prototype.stack`, so on RFEngine frames come
+//! from the stack of an `Error` passed to `console.*` (or thrown by a page
+//! script); a bare `console.log` carries none.
+
+use crate::ConsoleMessage;
+use sourcemap::DecodedMap;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Fetch callback used to load maps: `(url, resource_type)` to body.
+/// Returning `None` leaves that script unmapped.
+pub(crate) type Fetcher<'a> = &'a dyn Fn(&str, &str) -> Option<String>;
+
+/// Per-engine cache of decoded source maps keyed by script URL.
+#[derive(Default)]
+pub(crate) struct SourceMapResolver {
+    // `None` records a script without a usable map so it isn't refetched.
+    maps: Mutex<HashMap<String, Option<Arc<DecodedMap>>>>,
+}
+
+impl SourceMapResolver {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Drop cached maps (called on navigation).
+    pub(crate) fn clear(&self) {
+        if let Ok(mut maps) = self.maps.lock() {
+            maps.clear();
+        }
+    }
+
+    /// Load the map `script` (served from `script_url`) references, unless
+    /// one was already loaded for that URL.
+    pub(crate) fn preload(&self, script_url: &str, script: &str, fetch: Fetcher<'_>) {
+        match self.maps.lock() {
+            Ok(maps) if !maps.contains_key(script_url) => {}
+            _ => return,
+        }
+        let loaded = load_map(script_url, script, fetch).map(Arc::new);
+        if let Ok(mut maps) = self.maps.lock() {
+            maps.insert(script_url.to_string(), loaded);
+        }
+    }
+
+    /// Rewrite `msg.frames` (and the top-frame `source`/`line`/`column`) to
+    /// original positions where a preloaded source map is available.
+    pub(crate) fn resolve(&self, msg: &mut ConsoleMessage) {
+        let mut changed = false;
+        for frame in msg.frames.iter_mut() {
+            let Some(map) = self.map_for(&frame.source) else {
+                continue;
+            };
+            // Stack positions are 1-based, source maps are 0-based
+            let token =
+                map.lookup_token(frame.line.saturating_sub(1), frame.column.saturating_sub(1));
+            if let Some(token) = token {
+                if let Some(src) = token.get_source() {
+                    frame.source = src.to_string();
+                    frame.line = token.get_src_line() + 1;
+                    frame.column = token.get_src_col() + 1;
+                    changed = true;
+                }
+            }
+        }
+        if changed {
+            if let Some(top) = msg.frames.first() {
+                msg.source = Some(top.source.clone());
+                msg.line = Some(top.line);
+                msg.column = Some(top.column);
+            }
+        }
+    }
+
+    fn map_for(&self, script_url: &str) -> Option<Arc<DecodedMap>> {
+        self.maps.lock().ok()?.get(script_url).cloned().flatten()
+    }
+}
+
+// Find the `sourceMappingURL` of `script` and decode the referenced map
+// (inline `data:` URL or a URL relative to the script).
+fn load_map(script_url: &str, script: &str, fetch: Fetcher<'_>) -> Option<DecodedMap> {
+    let reference = sourcemap::locate_sourcemap_reference_slice(script.as_bytes()).ok()??;
+    let map_url = reference.get_url();
+    if map_url.starts_with("data:") {
+        return sourcemap::decode_data_url(map_url).ok();
+    }
+    let resolved = url::Url::parse(script_url)
+        .and_then(|base| base.join(map_url))
+        .map(|u| u.to_string())
+        .unwrap_or_else(|_| map_url.to_string());
+    let body = fetch(&resolved, "other")?;
+    sourcemap::decode_slice(body.as_bytes()).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StackFrame;
+    use base64::Engine as _;
+
+    #[test]
+    fn test_resolve_inline_data_url_map() {
+        // Generated line 1 maps to src/app.ts line 1, generated line 2 to line 3
+        let map = r#"{"version":3,"file":"app.min.js","sources":["src/app.ts"],"names":[],"mappings":"AAAA;AAEA"}"#;
+        let script = format!(
+            "var a=1;\nconsole.log(a);\n//# sourceMappingURL=data:application/json;base64,{}\n",
+            base64::engine::general_purpose::STANDARD.encode(map)
+        );
+        let fetched = std::cell::Cell::new(0);
+        let fetch = |_url: &str, _kind: &str| {
+            fetched.set(fetched.get() + 1);
+            None
+        };
+
+        let frame = StackFrame {
+            function: Some("main".into()),
+            source: "http://x/app.min.js".into(),
+            line: 2,
+            column: 1,
+        };
+        let mut msg = ConsoleMessage {
+            level: "log".into(),
+            text: "1".into(),
+            source: Some(frame.source.clone()),
+            line: Some(2),
+            column: Some(1),
+            stack: None,
+            group_depth: 0,
+            timestamp_ms: 0,
+            frames: vec![frame],
+        };
+
+        let original = msg.clone();
+        let resolver = SourceMapResolver::new();
+
+        // Nothing is fetched while resolving; unknown scripts stay as they are
+        resolver.resolve(&mut msg);
+        assert_eq!(msg.frames, original.frames);
+        assert_eq!(msg.line, Some(2));
+
+        resolver.preload("http://x/app.min.js", &script, &fetch);
+        resolver.resolve(&mut msg);
+        assert_eq!(msg.source.as_deref(), Some("src/app.ts"));
+        assert_eq!(msg.line, Some(3));
+        assert_eq!(msg.column, Some(1));
+        assert_eq!(msg.frames[0].function.as_deref(), Some("main"));
+
+        // An inline map needs no fetch, and the map is kept per script URL
+        resolver.preload("http://x/app.min.js", "", &fetch);
+        let mut again = original;
+        resolver.resolve(&mut again);
+        assert_eq!(again.line, Some(3));
+        assert_eq!(fetched.get(), 0);
+    }
+
+    #[test]
+    fn test_preload_fetches_map_relative_to_script() {
+        let map = r#"{"version":3,"sources":["src/app.ts"],"names":[],"mappings":"AAAA,6BAEI"}"#;
+        let requested = std::cell::RefCell::new(Vec::new());
+        let fetch = |url: &str, kind: &str| {
+            requested
+                .borrow_mut()
+                .push((url.to_string(), kind.to_string()));
+            (url == "http://x/js/app.min.js.map").then(|| map.to_string())
+        };
+        let resolver = SourceMapResolver::new();
+        resolver.preload(
+            "http://x/js/app.min.js",
+            "main();\n//# sourceMappingURL=app.min.js.map\n",
+            &fetch,
+        );
+        assert_eq!(
+            *requested.borrow(),
+            vec![(
+                "http://x/js/app.min.js.map".to_string(),
+                "other".to_string()
+            )]
+        );
+
+        let mut msg = ConsoleMessage {
+            level: "error".into(),
+            text: "boom".into(),
+            source: None,
+            line: None,
+            column: None,
+            stack: None,
+            group_depth: 0,
+            timestamp_ms: 0,
+            frames: vec![StackFrame {
+                function: None,
+                source: "http://x/js/app.min.js".into(),
+                line: 1,
+                column: 30,
+            }],
+        };
+        resolver.resolve(&mut msg);
+        assert_eq!(msg.source.as_deref(), Some("src/app.ts"));
+        assert_eq!((msg.line, msg.column), (Some(3), Some(5)));
+    }
+}
@@ -0,0 +1,101 @@
+//! Line and word diff behind `TextSnapshot::diff`
+//!
+//! Snapshot text is compared line by line, whitespace-collapsed, with a
+//! longest-common-subsequence match. Pages whose markup has no line breaks
+//! come out as one long line, so when either side has a single line the
+//! words are compared instead.
+
+// Above this many LCS cells (after trimming the common prefix and suffix)
+// the differing middle is reported whole instead of matched
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// (added, removed) text of `new` compared to `old`: whole lines joined with
+/// newlines, or words joined with spaces when comparing by word.
+pub(crate) fn diff_text(old: &str, new: &str) -> (String, String) {
+    let lines = |text: &str| -> Vec<String> {
+        text.lines()
+            .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|l| !l.is_empty())
+            .collect()
+    };
+    let (old_lines, new_lines) = (lines(old), lines(new));
+    if old_lines.len() <= 1 || new_lines.len() <= 1 {
+        let old_words: Vec<&str> = old.split_whitespace().collect();
+        let new_words: Vec<&str> = new.split_whitespace().collect();
+        let (added, removed) = diff(&old_words, &new_words);
+        return (added.join(" "), removed.join(" "));
+    }
+    let (added, removed) = diff(&old_lines, &new_lines);
+    (added.join("\n"), removed.join("\n"))
+}
+
+// Items only in `new` and items only in `old`, each in order
+fn diff<T: PartialEq + Clone>(old: &[T], new: &[T]) -> (Vec<T>, Vec<T>) {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let (old, new) = (&old[prefix..], &new[prefix..]);
+    let suffix = old
+        .iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old, new) = (&old[..old.len() - suffix], &new[..new.len() - suffix]);
+    if old.len() * new.len() > MAX_DIFF_CELLS {
+        return (new.to_vec(), old.to_vec());
+    }
+
+    // lcs[i * width + j]: length of the LCS of old[i..] and new[j..]
+    let width = new.len() + 1;
+    let mut lcs = vec![0u32; (old.len() + 1) * width];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i * width + j] = if old[i] == new[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+    let (mut added, mut removed) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            removed.push(old[i].clone());
+            i += 1;
+        } else {
+            added.push(new[j].clone());
+            j += 1;
+        }
+    }
+    removed.extend_from_slice(&old[i..]);
+    added.extend_from_slice(&new[j..]);
+    (added, removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_text_by_line_and_by_word() {
+        let old = "Header\n  Price: $10  \nIn stock\n\nFooter";
+        let new = "Header\nPrice: $12\nIn stock\nShips today\nFooter";
+        assert_eq!(
+            diff_text(old, new),
+            (
+                "Price: $12\nShips today".to_string(),
+                "Price: $10".to_string()
+            )
+        );
+        assert_eq!(diff_text(old, old), (String::new(), String::new()));
+
+        // Single-line text is compared word by word
+        assert_eq!(
+            diff_text("Price: $10 In stock", "Price: $12 In stock Today"),
+            ("$12 Today".to_string(), "$10".to_string())
+        );
+    }
+}
@@ -0,0 +1,294 @@
+//! Lightweight TypeScript type stripping for `EngineConfig::strip_types`
+//!
+//! This is a token-level transform, not a compiler: it removes
+//!
+//! - `: T` annotations on `let`/`const`/`var` bindings and on function
+//!   parameters (including optional `a?: T`)
+//! - return type annotations, when the type is followed by `{` or `=>`
+//! - `interface` declarations and `type X = ...` aliases at statement start
+//!
+//! Anything else (`as` casts, generics on calls, enums, decorators) is left
+//! untouched and will fail to parse in the JS engine. Strings, template
+//! literals and comments are never modified.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Ident,
+    Punct,
+    Literal,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Token<'a> {
+    kind: Kind,
+    text: &'a str,
+    start: usize,
+    end: usize,
+}
+
+/// Strip type annotations and type-only declarations from `source`.
+pub(crate) fn strip_types(source: &str) -> String {
+    let tokens = tokenize(source);
+    let mut removed: Vec<(usize, usize)> = Vec::new();
+    // Innermost open bracket per nesting level
+    let mut brackets: Vec<&str> = Vec::new();
+    let mut i = 0;
+    while i < tokens.len() {
+        let tok = tokens[i];
+        if let Some(end) = type_declaration_end(&tokens, i) {
+            removed.push((tok.start, tokens[end - 1].end));
+            i = end;
+            continue;
+        }
+        if tok.text == ":" && tok.kind == Kind::Punct {
+            if let Some(first) = annotation_start(&tokens, i, brackets.last().copied()) {
+                let end = type_end(&tokens, i + 1);
+                if end > i + 1 {
+                    removed.push((tokens[first].start, tokens[end - 1].end));
+                    i = end;
+                    continue;
+                }
+            } else if i > 0 && tokens[i - 1].text == ")" {
+                // Return type: only when a body or arrow follows the type
+                let end = type_end(&tokens, i + 1);
+                if end > i + 1 && matches!(text_at(&tokens, end), Some("{") | Some("=>")) {
+                    removed.push((tok.start, tokens[end - 1].end));
+                    i = end;
+                    continue;
+                }
+            }
+        }
+        match tok.text {
+            "(" | "[" | "{" if tok.kind == Kind::Punct => brackets.push(tok.text),
+            ")" | "]" | "}" if tok.kind == Kind::Punct => {
+                brackets.pop();
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    let mut out = String::with_capacity(source.len());
+    let mut pos = 0;
+    for (start, end) in removed {
+        out.push_str(&source[pos..start]);
+        pos = end;
+    }
+    out.push_str(&source[pos..]);
+    out
+}
+
+fn text_at<'a>(tokens: &[Token<'a>], i: usize) -> Option<&'a str> {
+    tokens.get(i).map(|t| t.text)
+}
+
+fn is_ident(tokens: &[Token], i: usize) -> bool {
+    tokens.get(i).is_some_and(|t| t.kind == Kind::Ident)
+}
+
+// Index of the first token to remove when the `:` at `colon` starts a binding
+// or parameter annotation (the `?` of an optional parameter is included).
+fn annotation_start(tokens: &[Token], colon: usize, bracket: Option<&str>) -> Option<usize> {
+    let (name, first) = match colon.checked_sub(1).map(|p| tokens[p].text) {
+        Some("?") if colon >= 2 => (colon - 2, colon - 1),
+        _ => (colon.checked_sub(1)?, colon),
+    };
+    if !is_ident(tokens, name) {
+        return None;
+    }
+    let before = name.checked_sub(1).map(|p| tokens[p].text);
+    let declared = matches!(before, Some("let") | Some("const") | Some("var"));
+    let parameter = bracket == Some("(") && matches!(before, Some("(") | Some(","));
+    (declared || parameter).then_some(first)
+}
+
+// End (exclusive) of an `interface` or `type` alias declaration starting at
+// `i`, if one starts there.
+fn type_declaration_end(tokens: &[Token], i: usize) -> Option<usize> {
+    let at_statement_start = i == 0 || matches!(tokens[i - 1].text, ";" | "{" | "}");
+    if !at_statement_start || !is_ident(tokens, i + 1) {
+        return None;
+    }
+    match tokens[i].text {
+        "interface" => {
+            let open = (i + 2..tokens.len()).find(|&j| tokens[j].text == "{")?;
+            Some(balanced_end(tokens, open))
+        }
+        "type" => {
+            let mut j = i + 2;
+            if text_at(tokens, j) == Some("<") {
+                j = balanced_end(tokens, j);
+            }
+            if text_at(tokens, j) != Some("=") {
+                return None;
+            }
+            let end = type_end(tokens, j + 1);
+            Some(if text_at(tokens, end) == Some(";") {
+                end + 1
+            } else {
+                end
+            })
+        }
+        _ => None,
+    }
+}
+
+// End (exclusive) of the type expression starting at `i`; `i` when there is
+// none.
+fn type_end(tokens: &[Token], mut i: usize) -> usize {
+    if matches!(text_at(tokens, i), Some("|") | Some("&")) {
+        i += 1;
+    }
+    loop {
+        let end = primary_type_end(tokens, i);
+        if end == i {
+            return i;
+        }
+        i = end;
+        while text_at(tokens, i) == Some("[") && text_at(tokens, i + 1) == Some("]") {
+            i += 2;
+        }
+        match text_at(tokens, i) {
+            Some("|") | Some("&") => i += 1,
+            _ => return i,
+        }
+    }
+}
+
+fn primary_type_end(tokens: &[Token], i: usize) -> usize {
+    let Some(tok) = tokens.get(i) else {
+        return i;
+    };
+    match (tok.kind, tok.text) {
+        (Kind::Ident, "typeof" | "keyof" | "readonly") => primary_type_end(tokens, i + 1),
+        (Kind::Ident, _) => {
+            let mut j = i + 1;
+            while text_at(tokens, j) == Some(".") && is_ident(tokens, j + 1) {
+                j += 2;
+            }
+            if text_at(tokens, j) == Some("<") {
+                j = balanced_end(tokens, j);
+            }
+            j
+        }
+        (Kind::Literal, _) => i + 1,
+        (Kind::Punct, "{" | "[") => balanced_end(tokens, i),
+        (Kind::Punct, "(") => {
+            let j = balanced_end(tokens, i);
+            if text_at(tokens, j) == Some("=>") {
+                type_end(tokens, j + 1)
+            } else {
+                j
+            }
+        }
+        _ => i,
+    }
+}
+
+// Index just past the bracket matching the opener at `open`.
+fn balanced_end(tokens: &[Token], open: usize) -> usize {
+    let (opener, closer) = match tokens[open].text {
+        "(" => ("(", ")"),
+        "[" => ("[", "]"),
+        "<" => ("<", ">"),
+        _ => ("{", "}"),
+    };
+    let mut depth = 0;
+    for (j, tok) in tokens.iter().enumerate().skip(open) {
+        if tok.kind != Kind::Punct {
+            continue;
+        }
+        if tok.text == opener {
+            depth += 1;
+        } else if tok.text == closer {
+            depth -= 1;
+            if depth == 0 {
+                return j + 1;
+            }
+        }
+    }
+    tokens.len()
+}
+
+// Significant tokens only; whitespace and comments are skipped.
+fn tokenize(source: &str) -> Vec<Token<'_>> {
+    let bytes = source.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let start = i;
+        let kind = if c.is_ascii_whitespace() {
+            i += 1;
+            continue;
+        } else if source[i..].starts_with("//") {
+            i = source[i..].find('\n').map_or(bytes.len(), |n| i + n);
+            continue;
+        } else if source[i..].starts_with("/*") {
+            i = source[i + 2..]
+                .find("*/")
+                .map_or(bytes.len(), |n| i + 2 + n + 2);
+            continue;
+        } else if matches!(c, b'"' | b'\'' | b'`') {
+            i += 1;
+            while i < bytes.len() && bytes[i] != c {
+                i += if bytes[i] == b'\\' { 2 } else { 1 };
+            }
+            i = (i + 1).min(bytes.len());
+            Kind::Literal
+        } else if c.is_ascii_digit() {
+            while i < bytes.len() && (bytes[i].is_ascii_alphanumeric() || bytes[i] == b'.') {
+                i += 1;
+            }
+            Kind::Literal
+        } else if c.is_ascii_alphabetic() || c == b'_' || c == b'$' || c >= 0x80 {
+            while i < bytes.len()
+                && (bytes[i].is_ascii_alphanumeric()
+                    || bytes[i] == b'_'
+                    || bytes[i] == b'$'
+                    || bytes[i] >= 0x80)
+            {
+                i += 1;
+            }
+            Kind::Ident
+        } else {
+            i += ["=>", "...", "?."]
+                .iter()
+                .find(|p| source[i..].starts_with(*p))
+                .map_or(1, |p| p.len());
+            Kind::Punct
+        };
+        tokens.push(Token {
+            kind,
+            text: &source[start..i],
+            start,
+            end: i,
+        });
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_types() {
+        assert_eq!(strip_types("const x: number = 2; x+1"), "const x = 2; x+1");
+        assert_eq!(
+            strip_types("function f(a: string, b?: Array<number>): string[] { return a; }"),
+            "function f(a, b) { return a; }"
+        );
+        assert_eq!(
+            strip_types("const g = (n: number): number => n * 2;"),
+            "const g = (n) => n * 2;"
+        );
+        assert_eq!(
+            strip_types("interface P { a: number }\ntype Id = string | number;\nlet p = 1;"),
+            "\n\nlet p = 1;"
+        );
+        // Plain JS is left alone
+        let js = "var o = {a: 1}; var t = o.a ? f(1) : 'x: y'; // c: d";
+        assert_eq!(strip_types(js), js);
+    }
+}
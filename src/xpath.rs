@@ -0,0 +1,390 @@
+//! Minimal XPath evaluation over a parsed `scraper::Html` document
+//!
+//! Supported subset:
+//!
+//! - absolute paths built from `/` (child) and `//` (descendant) steps
+//! - element steps by name or `*`
+//! - predicates `[@attr]`, `[@attr='v']` (single or double quotes) and
+//!   1-based positions `[n]`, applied left to right
+//! - a final `text()` step (text nodes, trimmed, empty ones dropped) or
+//!   `@attr` step (attribute values)
+//!
+//! A path ending in an element step yields each element's text content with
+//! whitespace collapsed. Results are in document order without duplicates.
+
+use crate::{Error, Result};
+use scraper::{ElementRef, Html};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Axis {
+    Child,
+    Descendant,
+}
+
+#[derive(Debug, PartialEq)]
+enum NodeTest {
+    Element(String),
+    Text,
+    Attribute(String),
+}
+
+#[derive(Debug, PartialEq)]
+enum Predicate {
+    HasAttr(String),
+    AttrEquals(String, String),
+    Position(usize),
+}
+
+#[derive(Debug, PartialEq)]
+struct Step {
+    axis: Axis,
+    test: NodeTest,
+    predicates: Vec<Predicate>,
+}
+
+// A context node: `None` is the document itself, which has the root element
+// as its only child.
+type Context<'a> = Option<ElementRef<'a>>;
+
+/// Evaluate `xpath` against `document`, returning text or attribute values.
+pub(crate) fn query(document: &Html, xpath: &str) -> Result<Vec<String>> {
+    let steps = parse(xpath)?;
+    let mut contexts: Vec<Context> = vec![None];
+    for step in &steps {
+        match &step.test {
+            NodeTest::Element(name) => {
+                let mut next: Vec<Context> = Vec::new();
+                let mut seen = HashSet::new();
+                for ctx in &contexts {
+                    for parent in axis_parents(document, *ctx, step.axis) {
+                        let candidates = children(document, parent)
+                            .into_iter()
+                            .filter(|e| name == "*" || e.value().name() == name)
+                            .collect();
+                        for el in apply_predicates(candidates, &step.predicates) {
+                            if seen.insert(el.id()) {
+                                next.push(Some(el));
+                            }
+                        }
+                    }
+                }
+                contexts = next;
+            }
+            NodeTest::Text => return Ok(texts(document, &contexts, step.axis)),
+            NodeTest::Attribute(attr) => {
+                // Nested contexts reach the same elements through `//`
+                let mut out = Vec::new();
+                let mut seen = HashSet::new();
+                for ctx in &contexts {
+                    for el in axis_parents(document, *ctx, step.axis)
+                        .into_iter()
+                        .flatten()
+                    {
+                        if !seen.insert(el.id()) {
+                            continue;
+                        }
+                        if let Some(v) = el.value().attr(attr) {
+                            out.push(v.to_string());
+                        }
+                    }
+                }
+                return Ok(out);
+            }
+        }
+    }
+    Ok(contexts
+        .into_iter()
+        .flatten()
+        .map(|el| {
+            el.text()
+                .collect::<String>()
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .collect())
+}
+
+// Nodes whose children a step examines: the context itself for `/`, the
+// context and all its descendant elements for `//`.
+fn axis_parents<'a>(document: &'a Html, ctx: Context<'a>, axis: Axis) -> Vec<Context<'a>> {
+    match axis {
+        Axis::Child => vec![ctx],
+        Axis::Descendant => {
+            let root = ctx.unwrap_or_else(|| document.root_element());
+            let mut parents: Vec<Context> = Vec::new();
+            if ctx.is_none() {
+                parents.push(None);
+            }
+            parents.extend(root.descendants().filter_map(ElementRef::wrap).map(Some));
+            parents
+        }
+    }
+}
+
+fn children<'a>(document: &'a Html, parent: Context<'a>) -> Vec<ElementRef<'a>> {
+    match parent {
+        None => vec![document.root_element()],
+        Some(el) => el.children().filter_map(ElementRef::wrap).collect(),
+    }
+}
+
+fn apply_predicates<'a>(
+    mut candidates: Vec<ElementRef<'a>>,
+    predicates: &[Predicate],
+) -> Vec<ElementRef<'a>> {
+    for pred in predicates {
+        candidates = match pred {
+            Predicate::HasAttr(a) => candidates
+                .into_iter()
+                .filter(|e| e.value().attr(a).is_some())
+                .collect(),
+            Predicate::AttrEquals(a, v) => candidates
+                .into_iter()
+                .filter(|e| e.value().attr(a) == Some(v.as_str()))
+                .collect(),
+            Predicate::Position(n) => candidates.get(n - 1).copied().into_iter().collect(),
+        };
+    }
+    candidates
+}
+
+// Text nodes under `contexts`, each reported once even when nested contexts
+// reach it through `//`
+fn texts(document: &Html, contexts: &[Context], axis: Axis) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut seen = HashSet::new();
+    for ctx in contexts {
+        let el = match ctx {
+            Some(el) => *el,
+            None if axis == Axis::Descendant => document.root_element(),
+            None => continue,
+        };
+        let nodes: Vec<_> = match axis {
+            Axis::Child => el.children().collect(),
+            Axis::Descendant => el.descendants().collect(),
+        };
+        out.extend(
+            nodes
+                .into_iter()
+                .filter(|n| seen.insert(n.id()))
+                .filter_map(|n| n.value().as_text().map(|t| t.trim()))
+                .filter(|t| !t.is_empty())
+                .map(str::to_string),
+        );
+    }
+    out
+}
+
+fn unsupported(xpath: &str, reason: &str) -> Error {
+    Error::Other(format!("Unsupported XPath '{}': {}", xpath, reason))
+}
+
+fn parse(xpath: &str) -> Result<Vec<Step>> {
+    let src = xpath.trim();
+    if !src.starts_with('/') {
+        return Err(unsupported(xpath, "only absolute paths are supported"));
+    }
+    let mut steps = Vec::new();
+    let mut rest = src;
+    while !rest.is_empty() {
+        let axis = if let Some(r) = rest.strip_prefix("//") {
+            rest = r;
+            Axis::Descendant
+        } else if let Some(r) = rest.strip_prefix('/') {
+            rest = r;
+            Axis::Child
+        } else {
+            return Err(unsupported(xpath, "expected '/'"));
+        };
+        // A step runs to the next '/' outside brackets and quotes
+        let mut depth = 0;
+        let mut quote: Option<char> = None;
+        let mut end = rest.len();
+        for (i, c) in rest.char_indices() {
+            match (quote, c) {
+                (Some(q), c) if c == q => quote = None,
+                (Some(_), _) => {}
+                (None, '\'' | '"') => quote = Some(c),
+                (None, '[') => depth += 1,
+                (None, ']') => depth -= 1,
+                (None, '/') if depth == 0 => {
+                    end = i;
+                    break;
+                }
+                _ => {}
+            }
+        }
+        let (token, remainder) = rest.split_at(end);
+        rest = remainder;
+        if let Some(last) = steps.last() {
+            if !matches!(
+                last,
+                Step {
+                    test: NodeTest::Element(_),
+                    ..
+                }
+            ) {
+                return Err(unsupported(xpath, "text() and @attr must be the last step"));
+            }
+        }
+        steps.push(parse_step(xpath, axis, token)?);
+    }
+    if steps.is_empty() {
+        return Err(unsupported(xpath, "empty path"));
+    }
+    Ok(steps)
+}
+
+fn parse_step(xpath: &str, axis: Axis, token: &str) -> Result<Step> {
+    let (name, mut preds_src) = match token.find('[') {
+        Some(i) => token.split_at(i),
+        None => (token, ""),
+    };
+    let test = if name == "text()" {
+        NodeTest::Text
+    } else if let Some(attr) = name.strip_prefix('@') {
+        if !is_name(attr) {
+            return Err(unsupported(xpath, "invalid attribute name"));
+        }
+        NodeTest::Attribute(attr.to_string())
+    } else if name == "*" || is_name(name) {
+        NodeTest::Element(name.to_ascii_lowercase())
+    } else {
+        return Err(unsupported(xpath, &format!("invalid step '{}'", token)));
+    };
+
+    let mut predicates = Vec::new();
+    while !preds_src.is_empty() {
+        let body_end = preds_src
+            .strip_prefix('[')
+            .and_then(|p| closing_bracket(p).map(|i| i + 1))
+            .ok_or_else(|| unsupported(xpath, "unbalanced predicate"))?;
+        predicates.push(parse_predicate(xpath, preds_src[1..body_end].trim())?);
+        preds_src = &preds_src[body_end + 1..];
+    }
+    if !predicates.is_empty() && !matches!(test, NodeTest::Element(_)) {
+        return Err(unsupported(
+            xpath,
+            "predicates are only supported on element steps",
+        ));
+    }
+    Ok(Step {
+        axis,
+        test,
+        predicates,
+    })
+}
+
+// Index of the `]` closing a predicate body, skipping quoted strings.
+fn closing_bracket(body: &str) -> Option<usize> {
+    let mut quote: Option<char> = None;
+    for (i, c) in body.char_indices() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"') => quote = Some(c),
+            (None, ']') => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_predicate(xpath: &str, body: &str) -> Result<Predicate> {
+    if let Ok(n) = body.parse::<usize>() {
+        if n == 0 {
+            return Err(unsupported(xpath, "positions are 1-based"));
+        }
+        return Ok(Predicate::Position(n));
+    }
+    let attr_expr = body
+        .strip_prefix('@')
+        .ok_or_else(|| unsupported(xpath, &format!("unsupported predicate '{}'", body)))?;
+    match attr_expr.split_once('=') {
+        None if is_name(attr_expr) => Ok(Predicate::HasAttr(attr_expr.to_string())),
+        None => Err(unsupported(xpath, "invalid attribute name")),
+        Some((attr, value)) => {
+            let attr = attr.trim();
+            let value = value.trim();
+            let unquoted = value
+                .strip_prefix('\'')
+                .and_then(|v| v.strip_suffix('\''))
+                .or_else(|| value.strip_prefix('"').and_then(|v| v.strip_suffix('"')));
+            match unquoted {
+                Some(v) if is_name(attr) => {
+                    Ok(Predicate::AttrEquals(attr.to_string(), v.to_string()))
+                }
+                _ => Err(unsupported(
+                    xpath,
+                    &format!("unsupported predicate '{}'", body),
+                )),
+            }
+        }
+    }
+}
+
+fn is_name(s: &str) -> bool {
+    !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><body>
+        <div id="hello">Hello <b>bold</b> world</div>
+        <ul class="menu">
+          <li><a href="/a">A</a></li>
+          <li><a href="/b" rel="next">B</a></li>
+          <li><a href="/c">C</a></li>
+        </ul>
+    </body></html>"#;
+
+    #[test]
+    fn test_query_supported_subset() {
+        let doc = Html::parse_document(PAGE);
+        assert_eq!(
+            query(&doc, "//div[@id='hello']/text()").unwrap(),
+            vec!["Hello", "world"]
+        );
+        assert_eq!(
+            query(&doc, "//div[@id=\"hello\"]").unwrap(),
+            vec!["Hello bold world"]
+        );
+        assert_eq!(query(&doc, "//li/a/@href").unwrap(), vec!["/a", "/b", "/c"]);
+        assert_eq!(query(&doc, "//ul/li[2]").unwrap(), vec!["B"]);
+        assert_eq!(query(&doc, "//a[@rel]/text()").unwrap(), vec!["B"]);
+        assert_eq!(
+            query(&doc, "/html/body/ul/li[3]/a/@href").unwrap(),
+            vec!["/c"]
+        );
+        assert_eq!(query(&doc, "//li[1]/*").unwrap(), vec!["A"]);
+        assert!(query(&doc, "//span").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_query_nested_contexts_without_duplicates() {
+        let doc =
+            Html::parse_document(r#"<div><p>outer</p><div><a href="/x">inner</a></div></div>"#);
+        assert_eq!(query(&doc, "//div//p").unwrap(), vec!["outer"]);
+        assert_eq!(
+            query(&doc, "//div//text()").unwrap(),
+            vec!["outer", "inner"]
+        );
+        assert_eq!(query(&doc, "//div//@href").unwrap(), vec!["/x"]);
+    }
+
+    #[test]
+    fn test_query_rejects_unsupported_syntax() {
+        let doc = Html::parse_document(PAGE);
+        assert!(query(&doc, "div").is_err());
+        assert!(query(&doc, "//li[last()]").is_err());
+        assert!(query(&doc, "//li[0]").is_err());
+        assert!(query(&doc, "//text()/b").is_err());
+        assert!(query(&doc, "//li[@class='x'").is_err());
+    }
+}
@@ -14,14 +14,29 @@ fn test_computed_style_golden() {
         let property = f.get("property").unwrap().as_str().unwrap();
         let expected = f.get("expected").unwrap().as_str().unwrap();
 
+        // Optional extra files (e.g. stylesheets) keyed by request path
+        let resources: Vec<(String, String)> = f
+            .get("resources")
+            .and_then(|r| r.as_object())
+            .map(|r| {
+                r.iter()
+                    .map(|(path, body)| (path.clone(), body.as_str().unwrap().to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
         let server = Server::http("0.0.0.0:0").unwrap();
         let addr = server.server_addr();
         let html = html.to_string();
 
         std::thread::spawn(move || {
-            if let Ok(request) = server.recv() {
-                let response = tiny_http::Response::from_string(html);
-                let _ = request.respond(response);
+            for request in server.incoming_requests() {
+                let body = resources
+                    .iter()
+                    .find(|(path, _)| path == request.url())
+                    .map(|(_, body)| body.clone())
+                    .unwrap_or_else(|| html.clone());
+                let _ = request.respond(tiny_http::Response::from_string(body));
             }
         });
 
@@ -0,0 +1,47 @@
+#![cfg(feature = "readability")]
+
+use rfheadless::readability::Article;
+use rfheadless::rfengine::RFEngine;
+use rfheadless::{Engine, EngineConfig};
+use std::fs;
+use std::path::PathBuf;
+
+fn article_for(name: &str) -> Article {
+    let html = fs::read_to_string(format!("tests/goldens/articles/{}.html", name))
+        .expect("read article fixture");
+    let mut engine = RFEngine::new(EngineConfig {
+        enable_javascript: false,
+        ..Default::default()
+    })
+    .expect("create engine");
+    engine
+        .load_html(&html, Some("https://example.com/article"))
+        .expect("load article fixture");
+    engine.extract_article().expect("extract article")
+}
+
+// Compare against `tests/goldens/articles/<name>.article.json`; set
+// UPDATE_GOLDENS to rewrite it.
+fn check_golden(name: &str) {
+    let article = article_for(name);
+    let path = PathBuf::from(format!("tests/goldens/articles/{}.article.json", name));
+    if std::env::var("UPDATE_GOLDENS").is_ok() {
+        let json = serde_json::to_string_pretty(&article).unwrap();
+        fs::write(&path, json + "\n").expect("write article golden");
+        return;
+    }
+    let expected: Article =
+        serde_json::from_str(&fs::read_to_string(&path).expect("read article golden"))
+            .expect("parse article golden");
+    assert_eq!(article, expected);
+}
+
+#[test]
+fn golden_news_article() {
+    check_golden("news");
+}
+
+#[test]
+fn golden_blog_post() {
+    check_golden("blog");
+}
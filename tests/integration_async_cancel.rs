@@ -0,0 +1,56 @@
+#![cfg(feature = "cdp")]
+//! Cancelling a slow async navigation
+
+use rfheadless::async_api::CancellationToken;
+use rfheadless::{Browser, Error};
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn test_goto_cancellable_resolves_promptly() {
+    // Requires Chrome; skipped in CI
+    if std::env::var("CI").is_ok() {
+        return;
+    }
+
+    // Holds every response for 10s
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let addr = server.server_addr();
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_secs(10));
+                let _ = request.respond(tiny_http::Response::from_string("<html>late</html>"));
+            });
+        }
+    });
+
+    let browser = match Browser::new(None).await {
+        Ok(b) => b,
+        Err(e) => {
+            eprintln!(
+                "Skipping cancellation test because Chrome is not available: {}",
+                e
+            );
+            return;
+        }
+    };
+    let page = browser.new_page().await.unwrap();
+
+    let token = CancellationToken::new();
+    let trigger = token.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        trigger.cancel();
+    });
+
+    let start = Instant::now();
+    let res = page
+        .goto_cancellable(&format!("http://{}/slow", addr), &token)
+        .await;
+    assert!(
+        matches!(&res, Err(Error::Other(msg)) if msg == "cancelled"),
+        "{:?}",
+        res
+    );
+    assert!(start.elapsed() < Duration::from_secs(3));
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "async")]
+//! The same async flow against every backend through `AsyncEngine`
+
+use rfheadless::{AsyncEngine, EngineConfig, ThreadedEngine};
+
+fn serve_page() -> String {
+    let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+    let addr = server.server_addr();
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = tiny_http::Response::from_string(
+                "<html><head><title>Unified</title></head><body><p>Same flow</p></body></html>",
+            )
+            .with_header(
+                tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"text/html"[..]).unwrap(),
+            );
+            let _ = request.respond(response);
+        }
+    });
+    format!("http://{}/", addr)
+}
+
+// Works against any backend: load, snapshot, close
+async fn run_flow(engine: &dyn AsyncEngine, url: &str) {
+    engine.load_url(url).await.expect("load_url failed");
+    let snapshot = engine.render_text_snapshot().await.unwrap();
+    assert_eq!(snapshot.title, "Unified");
+    assert!(snapshot.text.contains("Same flow"));
+    engine.close().await.unwrap();
+    assert!(engine.render_text_snapshot().await.is_err());
+}
+
+#[cfg(feature = "rfengine")]
+#[tokio::test]
+async fn test_async_flow_on_rfengine() {
+    // Skip on CI where network may not be available
+    if std::env::var("CI").is_ok() {
+        return;
+    }
+    let engine = ThreadedEngine::<rfheadless::rfengine::RFEngine>::new(EngineConfig::default())
+        .await
+        .expect("Failed to create RFEngine");
+    run_flow(&engine, &serve_page()).await;
+}
+
+#[cfg(feature = "cdp")]
+#[tokio::test]
+async fn test_async_flow_on_cdp() {
+    // Requires Chrome; skipped in CI
+    if std::env::var("CI").is_ok() {
+        return;
+    }
+    let engine =
+        match ThreadedEngine::<rfheadless::cdp::CdpEngine>::new(EngineConfig::default()).await {
+            Ok(e) => e,
+            Err(e) => {
+                eprintln!(
+                    "Skipping CDP async flow because Chrome is not available: {}",
+                    e
+                );
+                return;
+            }
+        };
+    run_flow(&engine, &serve_page()).await;
+}
@@ -0,0 +1,58 @@
+#![cfg(all(feature = "async", feature = "rfengine"))]
+//! Async facade over RFEngine
+
+use rfheadless::AsyncRFEngine;
+
+#[tokio::test]
+async fn test_async_rfengine_goto_and_snapshot() {
+    // Skip on CI where network may not be available
+    if std::env::var("CI").is_ok() {
+        return;
+    }
+
+    let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    let addr = server.server_addr();
+    std::thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let response = tiny_http::Response::from_string(
+                "<html><head><title>Async</title></head><body><p>Hello from tiny_http</p></body></html>",
+            );
+            let _ = request.respond(response);
+        }
+    });
+
+    let engine = AsyncRFEngine::new(None)
+        .await
+        .expect("Failed to create AsyncRFEngine");
+    let url = format!("http://{}/", addr);
+    engine.goto(&url).await.expect("goto failed");
+
+    // Clones drive the same engine
+    let snapshot = engine.clone().text_snapshot().await.unwrap();
+    assert_eq!(snapshot.title, "Async");
+    assert!(snapshot.text.contains("Hello from tiny_http"));
+    assert_eq!(snapshot.url, url);
+
+    engine
+        .set_cookie(rfheadless::CookieParam {
+            name: "k".into(),
+            value: "v".into(),
+            url: Some(url.clone()),
+            domain: None,
+            path: None,
+            secure: None,
+            http_only: None,
+            same_site: None,
+            expires: None,
+            priority: None,
+            partition_key: None,
+        })
+        .await
+        .unwrap();
+    let cookies = engine.get_cookies().await.unwrap();
+    assert!(cookies.iter().any(|c| c.name == "k" && c.value == "v"));
+
+    let other = engine.clone();
+    engine.close().await.unwrap();
+    assert!(other.text_snapshot().await.is_err());
+}
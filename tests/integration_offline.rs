@@ -0,0 +1,128 @@
+use rfheadless::fixtures::FixtureLoader;
+use rfheadless::rfengine::{RFEngine, ResourceLoader};
+use rfheadless::{Engine, EngineConfig};
+
+#[test]
+fn test_offline_fixtures_serve_page_and_stylesheet() {
+    // `.test` hosts never resolve, so everything must come from the fixtures
+    let mut engine = RFEngine::new(EngineConfig {
+        offline_fixtures: Some("tests/fixtures/offline".into()),
+        ..Default::default()
+    })
+    .expect("Failed to create RFEngine");
+    engine
+        .load_url("https://site.test/")
+        .expect("Failed to load fixture page");
+    assert_eq!(engine.render_text_snapshot().unwrap().title, "Offline");
+
+    let res = engine
+        .evaluate_script(
+            "(()=>{ return getComputedStyle(document.querySelector('.headline')).getPropertyValue('color'); })()",
+        )
+        .expect("Eval failed");
+    assert_eq!(res.value.trim().trim_matches('"'), "#336699");
+
+    // Pages without a fixture fail instead of going to the network
+    assert!(engine.load_url("https://site.test/missing.html").is_err());
+}
+
+#[test]
+fn test_recorded_responses_replay_offline() {
+    if std::env::var("CI").is_ok() {
+        return;
+    }
+    let dir = std::env::temp_dir().join(format!("rfh_record_{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    // Answers until unblocked; the port is closed before the replay
+    let server = std::sync::Arc::new(tiny_http::Server::http("127.0.0.1:0").unwrap());
+    let url = format!("http://{}/", server.server_addr());
+    let serving = server.clone();
+    let handle = std::thread::spawn(move || {
+        for request in serving.incoming_requests() {
+            let response = if request.url() == "/site.css" {
+                tiny_http::Response::from_string(".headline{color:#336699}")
+            } else {
+                tiny_http::Response::from_string(
+                    "<html><head><title>Recorded</title><link rel=\"stylesheet\" href=\"/site.css\"></head><body><h1 class=\"headline\">Hello</h1></body></html>",
+                )
+                .with_header("X-Fixture: page".parse::<tiny_http::Header>().unwrap())
+            };
+            let _ = request.respond(response);
+        }
+    });
+
+    let config = EngineConfig {
+        enable_javascript: false,
+        enable_preconnect: false,
+        ..Default::default()
+    };
+    let mut recorder = RFEngine::new(EngineConfig {
+        record_dir: Some(dir.clone()),
+        ..config.clone()
+    })
+    .expect("Failed to create RFEngine");
+    recorder.load_url(&url).expect("Failed to load URL");
+    let recorded = recorder.render_text_snapshot().unwrap();
+    server.unblock();
+    handle.join().unwrap();
+    drop(server);
+
+    let mut replay = RFEngine::new(EngineConfig {
+        offline_fixtures: Some(dir.clone()),
+        ..config
+    })
+    .expect("Failed to create RFEngine");
+    replay.load_url(&url).expect("Failed to replay URL");
+    let replayed = replay.render_text_snapshot().unwrap();
+    assert_eq!(
+        (replayed.title, replayed.text, replayed.url),
+        (recorded.title, recorded.text, recorded.url)
+    );
+
+    // Status and headers are kept next to the body
+    let (status, headers, body) = FixtureLoader::from_dir(&dir)
+        .fetch(&url, &Default::default())
+        .unwrap();
+    assert_eq!(status, 200);
+    assert_eq!(headers.get("x-fixture").map(String::as_str), Some("page"));
+    assert!(String::from_utf8(body)
+        .unwrap()
+        .contains("<title>Recorded</title>"));
+    let (_, _, css) = FixtureLoader::from_dir(&dir)
+        .fetch(&format!("{}site.css", url), &Default::default())
+        .unwrap();
+    assert_eq!(css, b".headline{color:#336699}");
+
+    let _ = std::fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn test_find_next_page_follows_pagination() {
+    let mut engine = RFEngine::new(EngineConfig {
+        offline_fixtures: Some("tests/fixtures/offline".into()),
+        enable_javascript: false,
+        ..Default::default()
+    })
+    .expect("Failed to create RFEngine");
+
+    let mut url = "https://site.test/blog/".to_string();
+    let mut visited = vec![url.clone()];
+    while let Some(next) = engine
+        .load_url(&url)
+        .and_then(|_| engine.find_next_page())
+        .expect("Failed to read pagination")
+    {
+        url = next;
+        visited.push(url.clone());
+        assert!(visited.len() <= 3, "pagination loops: {:?}", visited);
+    }
+    assert_eq!(
+        visited,
+        [
+            "https://site.test/blog/",
+            "https://site.test/blog/page/2/",
+            "https://site.test/blog/page/3/",
+        ]
+    );
+}
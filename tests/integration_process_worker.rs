@@ -0,0 +1,26 @@
+//! Process-worker spawn failures must not panic the engine
+
+use rfheadless::rfengine::RFEngine;
+use rfheadless::{Engine, EngineConfig};
+
+#[test]
+fn test_unspawnable_guessed_worker_falls_back_to_thread() {
+    // This test binary is its own process, so pointing the guess at a missing
+    // binary can't affect other tests.
+    std::env::set_var(
+        "CARGO_BIN_EXE_rfheadless",
+        std::env::temp_dir().join("rfh-missing-worker"),
+    );
+    let mut engine = RFEngine::new(EngineConfig {
+        use_process_worker: true,
+        enable_js_isolation: false,
+        ..Default::default()
+    })
+    .expect("engine should fall back to an in-thread worker");
+    engine
+        .load_html("<html><body><p>x</p></body></html>", None)
+        .expect("load_html failed");
+    let res = engine.evaluate_script("1 + 1").expect("eval failed");
+    assert!(!res.is_error, "{}", res.value);
+    assert_eq!(res.value, "2");
+}
@@ -1,4 +1,6 @@
-use std::time::Instant;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[test]
 fn test_stylesheet_parallel_load_time() {
@@ -61,3 +63,88 @@ fn test_stylesheet_parallel_load_time() {
 
     assert!(elapsed < 400, "expected load < 400ms, got {}ms", elapsed);
 }
+
+// Page linking `style_count` stylesheets that each take `latency` to serve
+fn spawn_style_server(style_count: usize, latency: Duration) -> String {
+    let server = tiny_http::Server::http("0.0.0.0:0").unwrap();
+    let addr = server.server_addr();
+    let links: String = (0..style_count)
+        .map(|i| format!("<link rel=\"stylesheet\" href=\"/s{}.css\">", i))
+        .collect();
+    let html = format!(
+        "<html><head><title>T</title>{}</head><body></body></html>",
+        links
+    );
+    std::thread::spawn(move || {
+        for req in server.incoming_requests() {
+            let html = html.clone();
+            std::thread::spawn(move || {
+                if req.url().ends_with(".css") {
+                    std::thread::sleep(latency);
+                    let _ = req.respond(tiny_http::Response::from_string("body{color:blue}"));
+                } else {
+                    let _ = req.respond(tiny_http::Response::from_string(html));
+                }
+            });
+        }
+    });
+    format!("http://{}/", addr)
+}
+
+#[test]
+fn test_blocking_stylesheet_fetch_keeps_up_with_runtime_path() {
+    if std::env::var("CI").is_ok() {
+        return;
+    }
+
+    use rfheadless::rfengine::{MetricEvent, RFEngine};
+    use rfheadless::{Engine, EngineConfig};
+
+    let style_count = 16;
+    let url = spawn_style_server(style_count, Duration::from_millis(30));
+
+    // Load time and number of stylesheets downloaded, with and without the runtime
+    let run = |enable_persistent_runtime: bool| {
+        let mut engine = RFEngine::new(EngineConfig {
+            enable_persistent_runtime,
+            enable_javascript: false,
+            enable_preconnect: false,
+            stylesheet_fetch_concurrency: 4,
+            ..Default::default()
+        })
+        .expect("failed to create engine");
+        let fetched = Arc::new(AtomicUsize::new(0));
+        let counter = fetched.clone();
+        engine.set_metrics_sink(Box::new(move |event| {
+            if let MetricEvent::StylesheetFetched { .. } = event {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }
+        }));
+        let t0 = Instant::now();
+        engine.load_url(&url).expect("load failed");
+        (t0.elapsed(), fetched.load(Ordering::SeqCst))
+    };
+
+    let (blocking_time, blocking_fetched) = run(false);
+    assert_eq!(blocking_fetched, style_count);
+
+    // 16 sheets at 30ms each take ~480ms one by one; four threads need ~120ms
+    assert!(
+        blocking_time < Duration::from_millis(400),
+        "expected blocking load < 400ms, got {:?}",
+        blocking_time
+    );
+
+    // Without the `async` feature both runs would take the blocking path
+    #[cfg(feature = "async")]
+    {
+        let (runtime_time, runtime_fetched) = run(true);
+        assert_eq!(runtime_fetched, style_count);
+        assert!(
+            blocking_time < runtime_time * 2 + Duration::from_millis(100),
+            "blocking load took {:?}, runtime load {:?}",
+            blocking_time,
+            runtime_time
+        );
+    }
+}
@@ -170,6 +170,8 @@ fn test_custom_user_agent() {
             http_only: None,
             same_site: None,
             expires: None,
+            priority: None,
+            partition_key: None,
         }])
         .expect("Failed to set cookie");
 